@@ -0,0 +1,183 @@
+use std::sync::OnceLock;
+
+use image::imageops::{self, FilterType};
+use image::{ImageBuffer, Rgba, RgbaImage};
+use serde::Deserialize;
+
+/// How a layer's colour combines with what is below it, as in the W3C
+/// compositing specification. Computed in linear light.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlendMode {
+    /// The layer covers what is below.
+    #[default]
+    Normal,
+    /// Darkens like ink soaking into paper: white leaves the backdrop as is.
+    Multiply,
+    /// Keeps the darker of layer and backdrop.
+    Darken,
+    /// Inverse of multiply: lightens, black leaves the backdrop as is.
+    Screen,
+    /// Multiplies dark and screens light areas of the backdrop, adding contrast.
+    Overlay,
+}
+
+impl BlendMode {
+    /// Blended value of one unpremultiplied channel.
+    fn mix(self, backdrop: f32, source: f32) -> f32 {
+        match self {
+            BlendMode::Normal => source,
+            BlendMode::Multiply => backdrop * source,
+            BlendMode::Darken => backdrop.min(source),
+            BlendMode::Screen => backdrop + source - backdrop * source,
+            BlendMode::Overlay => {
+                if backdrop <= 0.5 {
+                    2.0 * backdrop * source
+                } else {
+                    1.0 - 2.0 * (1.0 - backdrop) * (1.0 - source)
+                }
+            }
+        }
+    }
+}
+
+/// Colour in linear light with premultiplied alpha, every component in `0..=1`.
+///
+/// Mixing has to happen in linear light: averaging gamma-encoded sRGB values
+/// makes antialiased edges too dark. Premultiplying keeps the colour of
+/// transparent pixels from bleeding into their neighbours.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct LinearColor {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+impl LinearColor {
+    pub fn from_srgba(pixel: Rgba<u8>) -> Self {
+        let a = pixel[3] as f32 / 255.0;
+        Self {
+            r: decode(pixel[0]) * a,
+            g: decode(pixel[1]) * a,
+            b: decode(pixel[2]) * a,
+            a,
+        }
+    }
+
+    pub fn to_srgba(self) -> Rgba<u8> {
+        if self.a <= 0.0 {
+            return Rgba([0, 0, 0, 0]);
+        }
+        Rgba([
+            encode(self.r / self.a),
+            encode(self.g / self.a),
+            encode(self.b / self.a),
+            (self.a * 255.0).round().clamp(0.0, 255.0) as u8,
+        ])
+    }
+
+    /// The colour made `opacity` times as opaque.
+    pub fn with_opacity(self, opacity: f32) -> Self {
+        Self {
+            r: self.r * opacity,
+            g: self.g * opacity,
+            b: self.b * opacity,
+            a: self.a * opacity,
+        }
+    }
+
+    /// `self` on top of `backdrop`, mixed with `mode` where both are present.
+    pub fn blend(self, backdrop: LinearColor, mode: BlendMode) -> Self {
+        if mode == BlendMode::Normal {
+            return self.over(backdrop);
+        }
+
+        let channel = |source: f32, below: f32| {
+            let straight_source = if self.a > 0.0 { source / self.a } else { 0.0 };
+            let straight_below = if backdrop.a > 0.0 { below / backdrop.a } else { 0.0 };
+            source * (1.0 - backdrop.a)
+                + below * (1.0 - self.a)
+                + self.a * backdrop.a * mode.mix(straight_below, straight_source)
+        };
+
+        Self {
+            r: channel(self.r, backdrop.r),
+            g: channel(self.g, backdrop.g),
+            b: channel(self.b, backdrop.b),
+            a: self.a + backdrop.a * (1.0 - self.a),
+        }
+    }
+
+    /// Porter-Duff "over": `self` on top of `backdrop`.
+    pub fn over(self, backdrop: LinearColor) -> Self {
+        let rest = 1.0 - self.a;
+        Self {
+            r: self.r + backdrop.r * rest,
+            g: self.g + backdrop.g * rest,
+            b: self.b + backdrop.b * rest,
+            a: self.a + backdrop.a * rest,
+        }
+    }
+}
+
+/// Composites `source`, made `opacity` times as opaque, onto `backdrop`.
+/// Both are straight-alpha sRGB, as stored in images.
+pub fn composite(backdrop: Rgba<u8>, source: Rgba<u8>, opacity: f32, mode: BlendMode) -> Rgba<u8> {
+    let opacity = opacity.clamp(0.0, 1.0);
+    if source[3] == 0 || opacity == 0.0 {
+        return backdrop;
+    }
+    if mode == BlendMode::Normal && source[3] == 255 && opacity == 1.0 {
+        return source;
+    }
+
+    LinearColor::from_srgba(source)
+        .with_opacity(opacity)
+        .blend(LinearColor::from_srgba(backdrop), mode)
+        .to_srgba()
+}
+
+/// Resizes in linear light with premultiplied alpha, so edges of transparent
+/// areas keep their colour instead of picking up a dark fringe.
+pub fn resize(image: &RgbaImage, width: u32, height: u32, filter: FilterType) -> RgbaImage {
+    let linear: ImageBuffer<Rgba<f32>, Vec<f32>> = ImageBuffer::from_fn(image.width(), image.height(), |x, y| {
+        let color = LinearColor::from_srgba(*image.get_pixel(x, y));
+        Rgba([color.r, color.g, color.b, color.a])
+    });
+
+    let resized = imageops::resize(&linear, width, height, filter);
+
+    ImageBuffer::from_fn(width, height, |x, y| {
+        let [r, g, b, a] = resized.get_pixel(x, y).0;
+        // Lanczos overshoots; keep the colour premultiplied and in range.
+        let a = a.clamp(0.0, 1.0);
+        LinearColor { r: r.clamp(0.0, a), g: g.clamp(0.0, a), b: b.clamp(0.0, a), a }.to_srgba()
+    })
+}
+
+/// sRGB to linear light.
+fn decode(value: u8) -> f32 {
+    static TABLE: OnceLock<[f32; 256]> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        std::array::from_fn(|i| {
+            let c = i as f32 / 255.0;
+            if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        })
+    })[value as usize]
+}
+
+/// Linear light to sRGB, rounded to the nearest level.
+fn encode(value: f32) -> u8 {
+    let c = value.clamp(0.0, 1.0);
+    let encoded = if c <= 0.003_130_8 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    };
+    (encoded * 255.0).round() as u8
+}
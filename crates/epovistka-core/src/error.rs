@@ -0,0 +1,43 @@
+use std::path::PathBuf;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum RenderError {
+    /// The registry holds no template with this id.
+    #[error("Unknown template '{0}'")]
+    UnknownTemplate(String),
+
+    /// A value the document needs cannot be laid out, such as text too long
+    /// for its lines or too long for a QR code.
+    #[error("{0}")]
+    InvalidValue(String),
+
+    /// A template manifest is malformed or describes something that cannot
+    /// be drawn.
+    #[error("{0}")]
+    Template(String),
+
+    /// A template file or directory could not be read.
+    #[error("Failed to read {}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    /// A template image could not be read or decoded.
+    #[error("Failed to open image {}: {source}", path.display())]
+    Image {
+        path: PathBuf,
+        #[source]
+        source: image::ImageError,
+    },
+
+    /// A font file is not a font the renderer can draw with.
+    #[error("Failed to load font {0}")]
+    Font(String),
+
+    /// Drawing or encoding the document failed.
+    #[error("{0}")]
+    Failed(String),
+}
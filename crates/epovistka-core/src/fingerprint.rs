@@ -0,0 +1,218 @@
+use std::f32::consts::PI;
+
+use image::imageops::{self, FilterType};
+use image::{GrayImage, RgbaImage};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use sha2::{Digest, Sha256};
+
+/// Cells per side of the pattern. Cells are laid out relative to the image
+/// size, so the pattern survives resizing.
+const GRID: usize = 32;
+/// Peak change of a pixel's brightness, in 8-bit levels.
+const AMPLITUDE: f32 = 3.0;
+/// Pixels per cell side the image is resampled to before detection.
+const SAMPLES_PER_CELL: usize = 8;
+/// Bump weight from which a sample counts as the middle of its cell.
+const MIDDLE_WEIGHT: f32 = 0.6;
+/// Bump weight up to which a sample counts as the border of its cell.
+const BORDER_WEIGHT: f32 = 0.25;
+/// Detection score from which an image counts as produced by this service.
+/// Scores of unmarked images follow a standard normal distribution, so one
+/// passes this by chance about once in 3.5 million checks. Marked documents
+/// score 10 to 20, even after JPEG recompression at quality 40 or resizing
+/// to 40%.
+pub const DETECTION_THRESHOLD: f32 = 5.0;
+
+/// Result of looking for the fingerprint in an image.
+#[derive(Debug, Clone, Copy)]
+pub struct Detection {
+    /// Agreement with the expected pattern, in standard deviations of what
+    /// unmarked images score.
+    pub score: f32,
+    /// How sure the detector is that the image carries the fingerprint, from
+    /// 0 to 1; above 0.99 at [`DETECTION_THRESHOLD`].
+    pub confidence: f32,
+}
+
+impl Detection {
+    pub fn detected(&self) -> bool {
+        self.score >= DETECTION_THRESHOLD
+    }
+}
+
+/// Invisible spread-spectrum mark keyed by a server secret: a grid of cells,
+/// each brightened or darkened by a few levels in a pseudorandom pattern
+/// derived from the secret. Cells are smooth bumps of about 30 pixels, low
+/// enough in frequency to survive JPEG recompression and moderate resizing.
+///
+/// Detection resamples the image to the grid, measures how much each cell
+/// stands out from its surroundings and correlates that with the pattern. It
+/// needs the secret but not the original image. Cropping breaks the
+/// alignment and with it the mark.
+#[derive(Clone)]
+pub struct Fingerprint {
+    pattern: Vec<f32>,
+}
+
+// The pattern is as good as the secret, keep it out of logs.
+impl std::fmt::Debug for Fingerprint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Fingerprint").finish_non_exhaustive()
+    }
+}
+
+impl Fingerprint {
+    pub fn new(secret: &str) -> Self {
+        let seed: [u8; 32] = Sha256::digest(secret.as_bytes()).into();
+        let mut rng = StdRng::from_seed(seed);
+        let pattern = (0..GRID * GRID)
+            .map(|_| if rng.random::<bool>() { 1.0 } else { -1.0 })
+            .collect();
+
+        Self { pattern }
+    }
+
+    /// Adds the pattern to the image's brightness. Alpha is left alone.
+    ///
+    /// Channels are first squeezed into `AMPLITUDE..=255 - AMPLITUDE`, which
+    /// is not noticeable, so the mark is not clipped away on white paper.
+    pub fn embed(&self, image: &mut RgbaImage) {
+        let (width, height) = image.dimensions();
+        let columns = cell_weights(width);
+        let rows = cell_weights(height);
+        let contrast = 1.0 - 2.0 * AMPLITUDE / 255.0;
+
+        for (x, y, pixel) in image.enumerate_pixels_mut() {
+            let (column, column_weight) = columns[x as usize];
+            let (row, row_weight) = rows[y as usize];
+            let delta = self.pattern[row * GRID + column] * AMPLITUDE * column_weight * row_weight;
+
+            for channel in &mut pixel.0[..3] {
+                *channel = (*channel as f32 * contrast + AMPLITUDE + delta).round().clamp(0.0, 255.0) as u8;
+            }
+        }
+    }
+
+    /// Looks for the pattern in a decoded image.
+    pub fn detect(&self, image: &GrayImage) -> Detection {
+        let size = (GRID * SAMPLES_PER_CELL) as u32;
+        let sampled = imageops::resize(image, size, size, FilterType::Triangle);
+        let weights = cell_weights(size);
+
+        // How much brighter the middle of each cell is than its border. Medians
+        // ignore the text and lines that cover part of a cell, and comparing
+        // against the cell's own border cancels the tone of the paper.
+        let mut middles = vec![Vec::new(); GRID * GRID];
+        let mut borders = vec![Vec::new(); GRID * GRID];
+        for (x, y, pixel) in sampled.enumerate_pixels() {
+            let (column, column_weight) = weights[x as usize];
+            let (row, row_weight) = weights[y as usize];
+            let weight = column_weight * row_weight;
+            let value = pixel.0[0] as f32;
+            if weight >= MIDDLE_WEIGHT {
+                middles[row * GRID + column].push(value);
+            } else if column_weight.min(row_weight) <= BORDER_WEIGHT {
+                borders[row * GRID + column].push(value);
+            }
+        }
+        let contrasts = middles
+            .iter_mut()
+            .zip(&mut borders)
+            .map(|(middle, border)| median(middle) - median(border));
+
+        // Sign test: only whether each cell agrees with the pattern counts, so
+        // cells swamped by content cannot outvote clean ones. Without the mark
+        // agreement is a coin toss and the score is standard normal.
+        let (agreement, cells) = contrasts
+            .zip(&self.pattern)
+            .filter(|(contrast, _)| *contrast != 0.0)
+            .fold((0.0, 0.0), |(agreement, cells), (contrast, sign)| {
+                (agreement + contrast.signum() * sign, cells + 1.0)
+            });
+        let score = if cells > 0.0 { agreement / f32::sqrt(cells) } else { 0.0 };
+
+        Detection { score, confidence: normal_cdf(score - DETECTION_THRESHOLD / 2.0) }
+    }
+}
+
+/// Cell index and bump weight (0 at cell edges, 1 in the middle) of every
+/// pixel along an axis of `length` pixels.
+fn cell_weights(length: u32) -> Vec<(usize, f32)> {
+    (0..length)
+        .map(|i| {
+            let position = (i as f32 + 0.5) * GRID as f32 / length as f32;
+            let cell = (position as usize).min(GRID - 1);
+            (cell, (PI * (position - cell as f32)).sin())
+        })
+        .collect()
+}
+
+fn median(values: &mut [f32]) -> f32 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.sort_by(f32::total_cmp);
+    values[values.len() / 2]
+}
+
+/// Standard normal cumulative distribution (Abramowitz and Stegun 7.1.26).
+fn normal_cdf(x: f32) -> f32 {
+    let z = x.abs() / std::f32::consts::SQRT_2;
+    let t = 1.0 / (1.0 + 0.327_591_1 * z);
+    let polynomial = t * (0.254_829_6 + t * (-0.284_496_7 + t * (1.421_413_7 + t * (-1.453_152 + t * 1.061_405_4))));
+    let erf = 1.0 - polynomial * (-z * z).exp();
+    if x >= 0.0 {
+        0.5 * (1.0 + erf)
+    } else {
+        0.5 * (1.0 - erf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::codecs::jpeg::JpegEncoder;
+    use image::{DynamicImage, Rgba};
+
+    use super::*;
+
+    /// Slightly noisy paper with lines of dark "text" across it.
+    fn page() -> RgbaImage {
+        let mut rng = StdRng::seed_from_u64(7);
+        RgbaImage::from_fn(800, 1120, |x, y| {
+            let text = y % 60 < 18 && (x / 14) % 7 != 0 && (80..720).contains(&x);
+            let tone = if text { 40 } else { 232 } + rng.random_range(0..8);
+            Rgba([tone, tone, tone.saturating_sub(6), 255])
+        })
+    }
+
+    fn luma(image: &RgbaImage) -> GrayImage {
+        DynamicImage::ImageRgba8(image.clone()).to_luma8()
+    }
+
+    #[test]
+    fn detects_marked_images_only() {
+        let fingerprint = Fingerprint::new("test secret");
+        let unmarked = page();
+        let mut marked = unmarked.clone();
+        fingerprint.embed(&mut marked);
+
+        let mut jpeg = Vec::new();
+        JpegEncoder::new_with_quality(&mut jpeg, 40)
+            .encode_image(&DynamicImage::ImageRgba8(marked.clone()).to_rgb8())
+            .unwrap();
+        let recompressed = image::load_from_memory(&jpeg).unwrap().to_luma8();
+        let shrunk = imageops::resize(&luma(&marked), 320, 448, FilterType::Triangle);
+
+        for (copy, image) in [("original", luma(&marked)), ("JPEG", recompressed), ("40%", shrunk)] {
+            let detection = fingerprint.detect(&image);
+            assert!(detection.detected(), "{} scored {}", copy, detection.score);
+            assert!(detection.confidence > 0.99);
+        }
+
+        let detection = fingerprint.detect(&luma(&unmarked));
+        assert!(detection.score < DETECTION_THRESHOLD, "unmarked scored {}", detection.score);
+        let detection = Fingerprint::new("other secret").detect(&luma(&marked));
+        assert!(detection.score < DETECTION_THRESHOLD, "other secret scored {}", detection.score);
+    }
+}
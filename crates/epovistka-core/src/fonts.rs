@@ -0,0 +1,103 @@
+use rusttype::Font;
+use tracing::{info, warn};
+
+use crate::error::RenderError;
+use crate::template::{ShapingMode, TemplateManifest};
+use crate::text::Shaper;
+
+/// Characters every template is expected to render: the Ukrainian alphabet,
+/// Latin with common diacritics, digits and document punctuation.
+const COVERAGE_SAMPLE: &str = concat!(
+    "АБВГҐДЕЄЖЗИІЇЙКЛМНОПРСТУФХЦЧШЩЬЮЯабвгґдеєжзиіїйклмнопрстуфхцчшщьюя",
+    "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz",
+    "ÀÁÂÄÇÈÉÊËÍÎÏÑÓÔÖÚÛÜàáâäçèéêëíîïñóôöúûüĄąĆćČčĘęŁłŃńŒœŚśŠšŹźŻżŽž",
+    "0123456789",
+    ".,:;!?'’\"«»„“”()[]-–—/№§%+&*@",
+);
+
+#[derive(Debug)]
+pub struct LoadedFont {
+    pub name: String,
+    pub font: Font<'static>,
+    /// Raw font file, kept for the shaper which parses OpenType tables itself.
+    pub data: Vec<u8>,
+}
+
+impl LoadedFont {
+    pub fn has_glyph(&self, c: char) -> bool {
+        self.font.glyph(c).id().0 != 0
+    }
+}
+
+/// Ordered list of fonts a template draws text with: the primary font, its
+/// fallbacks and finally the last-resort font. Each character is drawn with
+/// the first font in the chain that has a glyph for it.
+#[derive(Debug)]
+pub struct FontChain {
+    fonts: Vec<LoadedFont>,
+}
+
+impl FontChain {
+    pub fn load(manifest: &TemplateManifest) -> Result<Self, RenderError> {
+        let names = std::iter::once(&manifest.font)
+            .chain(&manifest.fallback_fonts)
+            .chain(&manifest.last_resort_font);
+
+        let mut fonts = Vec::new();
+        for name in names {
+            let path = manifest.resolve(name);
+            let data = std::fs::read(&path).map_err(|source| RenderError::Io { path, source })?;
+            let font = Font::try_from_vec(data.clone()).ok_or_else(|| RenderError::Font(name.clone()))?;
+
+            if manifest.shaping == ShapingMode::Harfbuzz && rustybuzz::Face::from_slice(&data, 0).is_none() {
+                warn!("Font {} cannot be shaped, falling back to basic layout", name);
+            }
+
+            fonts.push(LoadedFont { name: name.clone(), font, data });
+        }
+
+        Ok(Self { fonts })
+    }
+
+    pub fn shaper(&self, mode: ShapingMode) -> Shaper<'_> {
+        Shaper::new(&self.fonts, mode)
+    }
+
+    /// Logs which characters of the coverage sample and of `extra` text fall
+    /// back to secondary fonts and which no font in the chain can draw.
+    pub fn report_coverage(&self, template: &str, extra: &[&str]) {
+        let mut sample: Vec<char> = COVERAGE_SAMPLE.chars().collect();
+        sample.extend(extra.iter().flat_map(|text| text.chars()).filter(|c| !c.is_whitespace()));
+        sample.sort_unstable();
+        sample.dedup();
+
+        let mut missing = String::new();
+        let mut fallback: Vec<String> = vec![String::new(); self.fonts.len()];
+        for &c in &sample {
+            match self.fonts.iter().position(|font| font.has_glyph(c)) {
+                Some(0) => {}
+                Some(index) => fallback[index].push(c),
+                None => missing.push(c),
+            }
+        }
+
+        for (font, chars) in self.fonts.iter().zip(&fallback).filter(|(_, chars)| !chars.is_empty()) {
+            info!(
+                "Template '{}': {} characters are drawn with fallback font {}: {}",
+                template,
+                chars.chars().count(),
+                font.name,
+                chars
+            );
+        }
+
+        if !missing.is_empty() {
+            warn!(
+                "Template '{}': {} characters are missing from every font: {}",
+                template,
+                missing.chars().count(),
+                missing
+            );
+        }
+    }
+}
@@ -0,0 +1,1027 @@
+use image::{DynamicImage, Rgba, RgbaImage};
+use qrcode::QrCode;
+use rusttype::Scale;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::info;
+use chrono::prelude::*;
+
+use crate::error::RenderError;
+use crate::template::{
+    Anchor, DisclaimerSpec, FieldSpec, FitSpec, FitStrategy, ImageSpec, Jitter, LayerKind, QrSpec, RectSpec,
+    RequestField, TemplateManifest, Transform, ValueSource, WatermarkPlacement,
+};
+use crate::compositing;
+use crate::fingerprint::Fingerprint;
+use crate::fonts::FontChain;
+use crate::locale::{Locale, LocaleId};
+use crate::output::{self, OutputFormat, OutputOptions};
+use crate::perceptual_hash::{PerceptualHash, RegionHash};
+use crate::provenance::Provenance;
+use crate::layout_debug::{self, LayoutIssue};
+use crate::scene::{Bounds, Canvas, Placement};
+use crate::text::{self, FittedText, Shaper};
+
+/// Modules of light margin the QR specification asks for around a code.
+const QR_QUIET_ZONE: usize = 4;
+/// Widest a part of the page hashed on its own gets, relative to its height.
+/// Hashes squeeze their input into a square, which blurs a whole line of
+/// text together, so lines are hashed in pieces.
+const REGION_ASPECT: f32 = 2.0;
+
+#[derive(Debug)]
+pub struct ImageGenerator {
+    template: Arc<RgbaImage>,
+    /// Images of the template's image layers, by asset path.
+    images: HashMap<String, Arc<RgbaImage>>,
+    watermark: Arc<RgbaImage>,
+    fonts: Arc<FontChain>,
+    /// Parts of the page hashed on their own, see [`hash_regions`].
+    regions: Vec<[f32; 4]>,
+    manifest: TemplateManifest,
+}
+
+/// Encoded document together with the seed it was rendered with.
+#[derive(Debug)]
+pub struct GeneratedImage {
+    pub data: Vec<u8>,
+    pub seed: u64,
+    pub format: OutputFormat,
+    /// Identifier embedded in the document's metadata.
+    pub render_id: String,
+    pub hash: PerceptualHash,
+    /// Hashes of the parts of the page the template's variable fields are drawn in.
+    pub regions: Vec<RegionHash>,
+}
+
+/// A line of text as it was drawn, in image pixels. Kept alongside the raster
+/// so vector outputs can lay a selectable text layer over the image.
+#[derive(Debug, Clone)]
+pub struct TextRun {
+    pub text: String,
+    pub x: f32,
+    pub baseline: f32,
+    pub width: f32,
+    pub size: f32,
+    /// Clockwise rotation of the baseline in degrees.
+    pub rotation: f32,
+}
+
+impl TextRun {
+    /// The run as it appears on the page once its layer is placed.
+    fn placed(self, placement: &Placement) -> Self {
+        if placement.is_identity() {
+            return self;
+        }
+
+        let (x, baseline) = placement.apply(self.x, self.baseline);
+        Self {
+            x,
+            baseline,
+            width: self.width * placement.scale(),
+            size: self.size * placement.scale(),
+            rotation: self.rotation + placement.rotation(),
+            ..self
+        }
+    }
+}
+
+/// A line of a text field as it was laid out, for checking the template's
+/// coordinates.
+#[derive(Debug, Clone)]
+pub struct TextPlacement {
+    pub field: String,
+    pub text: String,
+    /// The line's anchor before jitter.
+    pub anchor: Anchor,
+    pub jitter: Jitter,
+    /// Pixel boxes of the drawn glyphs, on the layer's canvas.
+    pub glyphs: Vec<Bounds>,
+    /// Where the layer's canvas landed on the page.
+    pub placement: Placement,
+}
+
+/// Rendered document before encoding.
+#[derive(Debug)]
+pub struct RenderedPage {
+    pub image: RgbaImage,
+    pub text: Vec<TextRun>,
+    pub layout: Vec<TextPlacement>,
+}
+
+/// Layout debug render: the document with its text layout drawn over it.
+#[derive(Debug)]
+pub struct LayoutReport {
+    pub image: GeneratedImage,
+    pub issues: Vec<LayoutIssue>,
+}
+
+/// Text a layer wrote, in the coordinates of its canvas.
+#[derive(Debug, Default)]
+struct LayerText {
+    runs: Vec<TextRun>,
+    lines: Vec<TextPlacement>,
+}
+
+impl LayerText {
+    fn push(&mut self, field: &FieldSpec, anchor: Anchor, run: TextRun, glyphs: Vec<Bounds>) {
+        self.lines.push(TextPlacement {
+            field: field.name.clone(),
+            text: run.text.clone(),
+            anchor,
+            jitter: field.jitter,
+            glyphs,
+            placement: Placement::new(&Transform::default(), None),
+        });
+        self.runs.push(run);
+    }
+}
+
+/// The values a document is rendered with.
+#[derive(Debug, Clone, Default)]
+pub struct RenderRequest {
+    pub name: String,
+    pub address: String,
+    /// Seed for every random choice in the render; a fresh one when `None`.
+    pub seed: Option<u64>,
+    /// Overrides the template's locale for month names and number formats.
+    pub locale: Option<LocaleId>,
+}
+
+/// Settings of a single render that come from the caller rather than the template.
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    pub today: NaiveDate,
+    /// Recorded in the document's provenance metadata.
+    pub rendered_at: DateTime<Utc>,
+    pub output: OutputOptions,
+    /// Forces the watermark on or off; `None` keeps the template's setting.
+    pub watermark: Option<bool>,
+    /// Invisible mark added to the finished page, if configured.
+    pub fingerprint: Option<Arc<Fingerprint>>,
+}
+
+/// Per-render values that template fields can draw their text from.
+struct FieldValues<'a> {
+    request: &'a RenderRequest,
+    locale: &'static Locale,
+    number: u32,
+    date: NaiveDate,
+    time: NaiveTime,
+}
+
+impl<'a> FieldValues<'a> {
+    fn resolve(&self, source: &'a ValueSource) -> Cow<'a, str> {
+        let locale = self.locale;
+        match source {
+            ValueSource::Request { field: RequestField::Name } => Cow::Borrowed(&self.request.name),
+            ValueSource::Request { field: RequestField::Address } => Cow::Borrowed(&self.request.address),
+            ValueSource::Static { text } => Cow::Borrowed(text),
+            ValueSource::Number { grouped } => locale.format_number(self.number as u64, *grouped).into(),
+            ValueSource::Year => locale.format_date(self.date, "yy").into(),
+            ValueSource::Month => locale.format_date(self.date, "MMMM").into(),
+            ValueSource::Day => locale.format_date(self.date, "d").into(),
+            ValueSource::Date { pattern } => {
+                locale.format_date(self.date, pattern.as_deref().unwrap_or(locale.date_pattern)).into()
+            }
+            ValueSource::Time { pattern } => {
+                locale.format_time(self.time, pattern.as_deref().unwrap_or(locale.time_pattern)).into()
+            }
+        }
+    }
+}
+
+impl ImageGenerator {
+    pub fn from_manifest(manifest: TemplateManifest) -> Result<Self, RenderError> {
+        let open = |asset: &str| {
+            let path = manifest.resolve(asset);
+            image::open(&path).map_err(|source| RenderError::Image { path, source })
+        };
+        let template = open(&manifest.image)?.to_rgba8();
+
+        // Load the images of image layers, each file once
+        let mut images = HashMap::new();
+        for layer in &manifest.layers {
+            if let LayerKind::Image(spec) = &layer.kind {
+                if !images.contains_key(&spec.image) {
+                    let image = open(&spec.image)?;
+                    images.insert(spec.image.clone(), Arc::new(image.to_rgba8()));
+                }
+            }
+        }
+
+        // Load fonts
+        let fonts = FontChain::load(&manifest)?;
+        fonts.report_coverage(&manifest.name, &manifest.static_texts());
+
+        // Load the watermark image, or draw the watermark text once so it can be stamped like one
+        let watermark = match &manifest.watermark.image {
+            Some(image) => open(image)?.to_rgba8(),
+            None => {
+                let color = manifest.watermark.color.unwrap_or(manifest.color).into();
+                Self::draw_text_stamp(&fonts.shaper(manifest.shaping), &manifest.watermark.text, manifest.watermark.size, color)
+            }
+        };
+
+        Ok(Self {
+            regions: hash_regions(&manifest, template.width(), template.height()),
+            template: Arc::new(template),
+            images,
+            watermark: Arc::new(watermark),
+            fonts: Arc::new(fonts),
+            manifest,
+        })
+    }
+
+    pub fn manifest(&self) -> &TemplateManifest {
+        &self.manifest
+    }
+
+    pub fn template(&self) -> &RgbaImage {
+        &self.template
+    }
+
+    /// Renders and encodes a document. All randomness is drawn from one RNG
+    /// seeded with `request.seed` (or a fresh seed), so the same request and
+    /// seed always produce the same pixels. A seeded render also derives its
+    /// render id from the request, so with the same `rendered_at` the encoded
+    /// file is identical too; unseeded renders get a random id.
+    pub fn generate_image(
+        &self,
+        request: &RenderRequest,
+        options: &RenderOptions,
+    ) -> Result<GeneratedImage, RenderError> {
+        let seed = request.seed.unwrap_or_else(|| rand::rng().random());
+        let page = self.render(request, options, &mut StdRng::seed_from_u64(seed))?;
+        let image = self.finish(page, request, seed, options)?;
+
+        info!("Successfully generated image for: {} (seed {}, render {})", request.name, seed, image.render_id);
+        Ok(image)
+    }
+
+    /// Renders a document as PNG with every text line's anchor, box, jitter
+    /// envelope and field name drawn over it, and lists glyphs that land
+    /// outside the image or their box.
+    pub fn debug_layout(
+        &self,
+        request: &RenderRequest,
+        options: &RenderOptions,
+    ) -> Result<LayoutReport, RenderError> {
+        let seed = request.seed.unwrap_or_else(|| rand::rng().random());
+        let mut page = self.render(request, options, &mut StdRng::seed_from_u64(seed))?;
+
+        let issues = layout_debug::check(&page.layout, self.template.width(), self.template.height());
+        layout_debug::draw_overlay(&mut page.image, &page.layout, &issues, &self.shaper());
+
+        let options = RenderOptions {
+            output: OutputOptions { format: OutputFormat::Png, ..options.output },
+            fingerprint: None,
+            ..options.clone()
+        };
+        Ok(LayoutReport { image: self.finish(page, request, seed, &options)?, issues })
+    }
+
+    /// Fingerprints and encodes a rendered page.
+    fn finish(
+        &self,
+        mut page: RenderedPage,
+        request: &RenderRequest,
+        seed: u64,
+        options: &RenderOptions,
+    ) -> Result<GeneratedImage, RenderError> {
+        if let Some(fingerprint) = &options.fingerprint {
+            fingerprint.embed(&mut page.image);
+        }
+
+        let provenance = match request.seed {
+            Some(seed) => {
+                let key = format!(
+                    "{}\0{}\0{}\0{:?}\0{}\0{:?}\0{:?}\0{}",
+                    seed,
+                    request.name,
+                    request.address,
+                    request.locale,
+                    options.today,
+                    options.output,
+                    options.watermark,
+                    options.fingerprint.is_some(),
+                );
+                Provenance::seeded(self.manifest.id(), options.rendered_at, &key)
+            }
+            None => Provenance::new(self.manifest.id(), options.rendered_at),
+        };
+        let bytes = output::encode(&page, &self.manifest.page, options.output, &provenance)
+            .map_err(RenderError::Failed)?;
+
+        // Same conversion as decoded uploads get, so an unmodified file hashes identically
+        let luma = DynamicImage::ImageRgba8(page.image).to_luma8();
+
+        Ok(GeneratedImage {
+            data: bytes,
+            seed,
+            format: options.output.format,
+            render_id: provenance.render_id,
+            hash: PerceptualHash::of(&luma),
+            regions: self.regions.iter().filter_map(|bounds| RegionHash::of(&luma, *bounds)).collect(),
+        })
+    }
+
+    /// Renders a document with placeholder values to make sure every field and
+    /// asset of the template can actually be drawn.
+    pub fn check(&self) -> Result<(), RenderError> {
+        let request = RenderRequest {
+            name: "Шевченко Тарас Григорович".to_string(),
+            address: "вул. Хрещатик, 1, м. Київ".to_string(),
+            ..RenderRequest::default()
+        };
+        let options = RenderOptions {
+            today: NaiveDate::from_ymd_opt(2024, 1, 1).unwrap_or_default(),
+            rendered_at: DateTime::UNIX_EPOCH,
+            output: OutputOptions::default(),
+            watermark: Some(true),
+            fingerprint: None,
+        };
+
+        self.render(&request, &options, &mut StdRng::seed_from_u64(0)).map(|_| ())
+    }
+
+    fn render(&self, request: &RenderRequest, options: &RenderOptions, rng: &mut StdRng) -> Result<RenderedPage, RenderError> {
+        let number = rng.random_range(64*64..512*512);
+
+        let time = self.generate_time(rng);
+        let locale = Locale::get(request.locale.unwrap_or(self.manifest.locale));
+
+        let values = FieldValues {
+            request,
+            locale,
+            number,
+            date: options.today,
+            time,
+        };
+
+        // Start from a copy of the template
+        let mut page = RenderedPage {
+            image: self.template.as_ref().clone(),
+            text: Vec::new(),
+            layout: Vec::new(),
+        };
+        let watermarked = options.watermark.unwrap_or(self.manifest.watermark.enabled);
+
+        // Draw every layer on its own canvas, then composite it onto the page.
+        // The watermark is always the last layer, so nothing covers it.
+        let mut canvas = Canvas::new(page.image.width(), page.image.height());
+        for layer in &self.manifest.layers {
+            if matches!(layer.kind, LayerKind::Watermark) && !watermarked {
+                continue;
+            }
+
+            canvas.clear();
+            let mut written = LayerText::default();
+            self.draw_layer(&layer.kind, &mut canvas, &mut written, &values, rng)?;
+
+            let placement = Placement::new(&layer.transform, canvas.bounds());
+            placement.composite(&mut page.image, &canvas, layer.opacity, layer.blend);
+            page.text.extend(written.runs.into_iter().map(|run| run.placed(&placement)));
+            page.layout.extend(written.lines.into_iter().map(|line| TextPlacement { placement, ..line }));
+        }
+
+        if watermarked {
+            if let Some(disclaimer) = &self.manifest.watermark.disclaimer {
+                self.draw_disclaimer(&mut page, disclaimer);
+            }
+        }
+
+        Ok(page)
+    }
+
+    /// Draws what a layer holds onto `canvas`, adding the lines of text it
+    /// writes to `written`.
+    fn draw_layer(
+        &self,
+        layer: &LayerKind,
+        canvas: &mut Canvas,
+        written: &mut LayerText,
+        values: &FieldValues,
+        rng: &mut impl Rng,
+    ) -> Result<(), RenderError> {
+        match layer {
+            LayerKind::Text(field) => self.draw_field(canvas, written, field, values, rng),
+            LayerKind::Image(spec) => self.draw_image(canvas, spec, rng),
+            LayerKind::Rect(rect) => {
+                Self::draw_rect(canvas, rect);
+                Ok(())
+            }
+            LayerKind::Line(line) => {
+                let color = line.color.unwrap_or(self.manifest.color).into();
+                canvas.draw_line((line.from.x, line.from.y), (line.to.x, line.to.y), line.width, color);
+                Ok(())
+            }
+            LayerKind::Qr(qr) => self.draw_qr(canvas, qr, values),
+            LayerKind::Watermark => {
+                match self.manifest.watermark.placement {
+                    WatermarkPlacement::Scattered => self.draw_all_watermarks(canvas, rng),
+                    WatermarkPlacement::Tiled => self.draw_tiled_watermarks(canvas, rng),
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn generate_time(&self, rng: &mut impl Rng) -> NaiveTime {
+        let hour = rng.random_range(8..19); // 08 to 18
+        let minute = rng.random_range(0..12) * 5; // 00, 05, 10, ..., 55
+
+        NaiveTime::from_hms_opt(hour, minute, 0).unwrap_or_default()
+    }
+
+    fn draw_all_watermarks(&self, canvas: &mut Canvas, rng: &mut impl Rng) {
+
+        // Generate random number of watermarks (3-6 copies for better coverage)
+        let num_watermarks = rng.random_range(2..5);
+
+        let image_width = canvas.width() as f32;
+        let image_height = canvas.height() as f32;
+
+        // Define safe margins to ensure watermarks are fully visible
+        let margin_x = 100.0;
+        let margin_y = 100.0;
+
+        // Calculate safe area for watermark placement
+        let safe_width = image_width - 2.0 * margin_x;
+        let safe_height = image_height - 2.0 * margin_y;
+
+        // Track placed watermarks to avoid excessive overlap
+        let mut placed_positions = Vec::new();
+        let min_distance = 200.0; // Minimum distance between watermark centers
+
+        for _ in 0..num_watermarks {
+            let mut attempts = 0;
+            let max_attempts = 20; // Prevent infinite loop
+
+            let (x, y, scale_factor, rotation_degrees, opacity) = loop {
+                attempts += 1;
+                if attempts > max_attempts {
+                    // If we can't find a good position after many attempts,
+                    // use a random position even if it overlaps
+                    break self.generate_watermark_params(
+                        margin_x, margin_y, safe_width, safe_height, rng
+                    );
+                }
+
+                let params = self.generate_watermark_params(
+                    margin_x, margin_y, safe_width, safe_height, rng
+                );
+
+                // Check if this position is too close to existing watermarks
+                let too_close = placed_positions.iter().any(|(existing_x, existing_y)| {
+                    let distance = ((params.0 - existing_x).powi(2) + (params.1 - existing_y).powi(2)).sqrt();
+                    distance < min_distance
+                });
+
+                if !too_close {
+                    placed_positions.push((params.0, params.1));
+                    break params;
+                }
+
+                // For the first watermark, always accept it to ensure we have at least one
+                if placed_positions.is_empty() {
+                    placed_positions.push((params.0, params.1));
+                    break params;
+                }
+            };
+
+            self.draw_watermark_at_position(
+                canvas,
+                &self.scaled_watermark(scale_factor),
+                x,
+                y,
+                rotation_degrees,
+                opacity,
+            );
+        }
+    }
+
+    fn generate_watermark_params(
+        &self,
+        margin_x: f32,
+        margin_y: f32,
+        safe_width: f32,
+        safe_height: f32,
+        rng: &mut impl Rng,
+    ) -> (f32, f32, f32, f32, f32) {
+        let spec = &self.manifest.watermark;
+        let scale_factor = spec.scale.sample(rng);
+        let rotation_degrees = spec.rotation.sample(rng);
+        let opacity = spec.opacity.sample(rng);
+
+        // Calculate watermark dimensions for positioning
+        let watermark_width = self.watermark.width() as f32 * scale_factor;
+        let watermark_height = self.watermark.height() as f32 * scale_factor;
+
+        // Account for rotation by adding extra margin
+        let rotated_diagonal = (watermark_width.powi(2) + watermark_height.powi(2)).sqrt();
+        let extra_margin = rotated_diagonal * 0.6;
+
+        // Calculate adjusted safe area
+        let adj_safe_width = safe_width - 2.0 * extra_margin;
+        let adj_safe_height = safe_height - 2.0 * extra_margin;
+
+        // Ensure we have positive safe area
+        let adj_safe_width = adj_safe_width.max(watermark_width);
+        let adj_safe_height = adj_safe_height.max(watermark_height);
+
+        // Generate position within adjusted safe area
+        let x = if adj_safe_width > 0.0 {
+            margin_x + extra_margin + rng.random_range(0.0..adj_safe_width)
+        } else {
+            margin_x + extra_margin
+        };
+
+        let y = if adj_safe_height > 0.0 {
+            margin_y + extra_margin + rng.random_range(0.0..adj_safe_height)
+        } else {
+            margin_y + extra_margin
+        };
+
+        (x, y, scale_factor, rotation_degrees, opacity)
+    }
+
+    /// Covers the whole image with a grid of identical stamps. The grid is
+    /// shifted by a random offset so stamps do not land on the same spots in
+    /// every document.
+    fn draw_tiled_watermarks(&self, canvas: &mut Canvas, rng: &mut impl Rng) {
+        let spec = &self.manifest.watermark;
+        let scale_factor = spec.scale.sample(rng);
+        let rotation_degrees = spec.rotation.sample(rng);
+        let opacity = spec.opacity.sample(rng);
+
+        // Footprint of one rotated stamp
+        let radians = rotation_degrees.to_radians();
+        let width = self.watermark.width() as f32 * scale_factor;
+        let height = self.watermark.height() as f32 * scale_factor;
+        let step_x = width * radians.cos().abs() + height * radians.sin().abs() + spec.gap;
+        let step_y = width * radians.sin().abs() + height * radians.cos().abs() + spec.gap;
+
+        let offset_x = rng.random_range(0.0..step_x);
+        let offset_y = rng.random_range(0.0..step_y);
+        let stamp = self.scaled_watermark(scale_factor);
+
+        let mut row = 0;
+        let mut y = offset_y - step_y;
+        while y < canvas.height() as f32 + step_y {
+            // Stagger every other row by half a stamp
+            let mut x = offset_x - step_x + if row % 2 == 1 { step_x / 2.0 } else { 0.0 };
+            while x < canvas.width() as f32 + step_x {
+                self.draw_watermark_at_position(canvas, &stamp, x, y, rotation_degrees, opacity);
+                x += step_x;
+            }
+            y += step_y;
+            row += 1;
+        }
+    }
+
+    /// Extends the page with a coloured band along the bottom and writes the
+    /// disclaimer into it, shrinking the text if it is too wide.
+    fn draw_disclaimer(&self, page: &mut RenderedPage, disclaimer: &DisclaimerSpec) {
+        let (width, height) = page.image.dimensions();
+        let band_height = disclaimer.height.round() as u32;
+
+        let mut extended = RgbaImage::from_pixel(width, height + band_height, disclaimer.background.into());
+        image::imageops::replace(&mut extended, &page.image, 0, 0);
+        let mut canvas = Canvas::from_image(extended);
+
+        let shaper = self.shaper();
+        let padding = disclaimer.height * 0.2;
+        let fit = FitSpec {
+            strategies: vec![FitStrategy::Shrink, FitStrategy::Condense],
+            min_size: None,
+            max_condense: FitSpec::default().max_condense,
+        };
+        let fitted = text::fit(
+            &shaper,
+            &disclaimer.text,
+            disclaimer.size,
+            &fit,
+            width as f32 - 2.0 * padding,
+            disclaimer.height - padding,
+        );
+
+        // Centre the line in the band
+        let text_width = shaper.measure(&fitted.text, fitted.scale, fitted.spacing);
+        let v_metrics = shaper.v_metrics(fitted.scale);
+        let x = (width as f32 - text_width) / 2.0;
+        let y = height as f32 + (disclaimer.height - (v_metrics.ascent - v_metrics.descent)) / 2.0;
+
+        let (run, _) = Self::draw_text_at_position(&mut canvas, &shaper, &fitted, x, y, disclaimer.color.into());
+        page.image = canvas.into_image();
+        page.text.push(run);
+    }
+
+    /// Draws a single line of text onto a transparent canvas of its own size.
+    fn draw_text_stamp(shaper: &Shaper, text: &str, size: f32, color: Rgba<u8>) -> RgbaImage {
+        let scale = Scale::uniform(size);
+        let v_metrics = shaper.v_metrics(scale);
+        let padding = (size * 0.1).ceil();
+        let width = (shaper.measure(text, scale, 0.0) + 2.0 * padding).ceil().max(1.0) as u32;
+        let height = (v_metrics.ascent - v_metrics.descent + 2.0 * padding).ceil().max(1.0) as u32;
+
+        let mut stamp = RgbaImage::from_pixel(width, height, Rgba([color[0], color[1], color[2], 0]));
+        for glyph in shaper.layout(text, scale, 0.0, padding, padding + v_metrics.ascent) {
+            if let Some(bounding_box) = glyph.pixel_bounding_box() {
+                glyph.draw(|gx, gy, coverage| {
+                    let gx = gx as i32 + bounding_box.min.x;
+                    let gy = gy as i32 + bounding_box.min.y;
+
+                    if gx >= 0 && gx < width as i32 && gy >= 0 && gy < height as i32 {
+                        let pixel = stamp.get_pixel_mut(gx as u32, gy as u32);
+                        let alpha = (coverage * color[3] as f32).round() as u8;
+                        pixel[3] = pixel[3].max(alpha);
+                    }
+                });
+            }
+        }
+
+        stamp
+    }
+
+    fn scaled_watermark(&self, scale_factor: f32) -> RgbaImage {
+        let watermark = self.watermark.as_ref();
+
+        // Calculate new dimensions
+        let new_width = (watermark.width() as f32 * scale_factor) as u32;
+        let new_height = (watermark.height() as f32 * scale_factor) as u32;
+
+        // Resize watermark with better filter for scaling down
+        compositing::resize(
+            watermark,
+            new_width,
+            new_height,
+            image::imageops::FilterType::Lanczos3,
+        )
+    }
+
+    /// Stamps `resized_watermark` rotated around its centre, which lands on (`x`, `y`).
+    fn draw_watermark_at_position(
+        &self,
+        canvas: &mut Canvas,
+        resized_watermark: &RgbaImage,
+        x: f32,
+        y: f32,
+        rotation_degrees: f32,
+        opacity: f32,
+    ) {
+        let start_x = x as i32;
+        let start_y = y as i32;
+        let radians = rotation_degrees.to_radians();
+        let cos_angle = radians.cos();
+        let sin_angle = radians.sin();
+
+        // Calculate center of rotation (center of the watermark)
+        let center_x = resized_watermark.width() as f32 / 2.0;
+        let center_y = resized_watermark.height() as f32 / 2.0;
+
+        // Pre-calculate bounds for efficiency
+        let image_width = canvas.width() as i32;
+        let image_height = canvas.height() as i32;
+        let watermark_width = resized_watermark.width() as i32;
+        let watermark_height = resized_watermark.height() as i32;
+
+        // Calculate the bounding box of the rotated watermark to optimize iteration
+        let rotated_width = (watermark_width as f32 * cos_angle.abs() + watermark_height as f32 * sin_angle.abs()) as i32;
+        let rotated_height = (watermark_width as f32 * sin_angle.abs() + watermark_height as f32 * cos_angle.abs()) as i32;
+
+        let min_x = (start_x - rotated_width / 2).max(0);
+        let max_x = (start_x + rotated_width / 2).min(image_width);
+        let min_y = (start_y - rotated_height / 2).max(0);
+        let max_y = (start_y + rotated_height / 2).min(image_height);
+
+        // Iterate only over the relevant area where the watermark might be
+        for target_x in min_x..max_x {
+            for target_y in min_y..max_y {
+                // Calculate relative coordinates from watermark center
+                let rel_x = target_x - start_x;
+                let rel_y = target_y - start_y;
+
+                // Apply inverse rotation to find source pixel
+                let src_x_f = (rel_x as f32) * cos_angle + (rel_y as f32) * sin_angle + center_x;
+                let src_y_f = (-(rel_x as f32) * sin_angle + (rel_y as f32) * cos_angle) + center_y;
+
+                let src_x = src_x_f.round() as i32;
+                let src_y = src_y_f.round() as i32;
+
+                if src_x >= 0 && src_x < watermark_width &&
+                    src_y >= 0 && src_y < watermark_height {
+
+                    let pixel = resized_watermark.get_pixel(src_x as u32, src_y as u32);
+                    canvas.paint(target_x, target_y, *pixel, opacity);
+                }
+            }
+        }
+    }
+
+    fn shaper(&self) -> Shaper<'_> {
+        self.fonts.shaper(self.manifest.shaping)
+    }
+
+    fn draw_field(
+        &self,
+        canvas: &mut Canvas,
+        written: &mut LayerText,
+        field: &FieldSpec,
+        values: &FieldValues,
+        rng: &mut impl Rng,
+    ) -> Result<(), RenderError> {
+        let shaper = self.shaper();
+        let text = values.resolve(&field.source);
+        let text = text.as_ref();
+        let color = field.color.unwrap_or(self.manifest.color).into();
+
+        for position in &field.positions {
+            let dx = field.jitter.x.sample(rng);
+            let dy = field.jitter.y.sample(rng);
+            let size = field.size.sample(rng);
+
+            let fitted = if position.width.is_some() || position.height.is_some() {
+                // Jitter moves the text inside its box, so only the remaining room counts.
+                let width = position.width.map_or(f32::INFINITY, |w| w - dx.max(0.0));
+                let height = position.height.map_or(f32::INFINITY, |h| h - dy.max(0.0));
+                text::fit(&shaper, text, size, &field.fit, width, height)
+            } else {
+                FittedText {
+                    text: text.into(),
+                    scale: Scale::uniform(size),
+                    spacing: 0.0,
+                }
+            };
+
+            // Keep the baseline where the unshrunk text would have sat so
+            // smaller text still rests on the form line.
+            let baseline_shift = shaper.v_metrics(Scale::uniform(size)).ascent
+                - shaper.v_metrics(fitted.scale).ascent;
+
+            let (run, glyphs) = Self::draw_text_at_position(canvas, &shaper, &fitted, position.x + dx, position.y + dy + baseline_shift, color);
+            written.push(field, *position, run, glyphs);
+        }
+
+        for flow in &field.flows {
+            self.draw_flow(canvas, written, field, flow, text, rng)?;
+        }
+
+        Ok(())
+    }
+
+    /// Word-wraps `text` across the line boxes of `flow`, shrinking the font
+    /// down to the field's minimum size if the field allows it.
+    fn draw_flow(
+        &self,
+        canvas: &mut Canvas,
+        written: &mut LayerText,
+        field: &FieldSpec,
+        flow: &[Anchor],
+        text: &str,
+        rng: &mut impl Rng,
+    ) -> Result<(), RenderError> {
+        let shaper = &self.shaper();
+        let color = field.color.unwrap_or(self.manifest.color).into();
+        let size = field.size.sample(rng);
+        let min_size = if field.fit.strategies.contains(&FitStrategy::Shrink) {
+            field.fit.min_size.unwrap_or(size).min(size)
+        } else {
+            size
+        };
+
+        let offsets: Vec<(f32, f32)> = flow
+            .iter()
+            .map(|_| (field.jitter.x.sample(rng), field.jitter.y.sample(rng)))
+            .collect();
+        let widths: Vec<f32> = flow
+            .iter()
+            .zip(&offsets)
+            .map(|(line, (dx, _))| line.width.unwrap_or(f32::INFINITY) - dx.max(0.0))
+            .collect();
+
+        let mut current = size;
+        let (scale, lines) = loop {
+            let scale = Scale::uniform(current);
+            if let Some(lines) = text::wrap(shaper, text, scale, 0.0, &widths) {
+                break (scale, lines);
+            }
+            if current <= min_size {
+                return Err(RenderError::InvalidValue(format!(
+                    "Value of '{}' is too long to fit into {} lines",
+                    field.name,
+                    flow.len()
+                )));
+            }
+            current = (current - 1.0).max(min_size);
+        };
+
+        let baseline_shift = shaper.v_metrics(Scale::uniform(size)).ascent
+            - shaper.v_metrics(scale).ascent;
+
+        for ((line, anchor), (dx, dy)) in lines.into_iter().zip(flow).zip(offsets) {
+            let fitted = FittedText {
+                text: line.into(),
+                scale,
+                spacing: 0.0,
+            };
+            let (run, glyphs) = Self::draw_text_at_position(canvas, shaper, &fitted, anchor.x + dx, anchor.y + dy + baseline_shift, color);
+            written.push(field, *anchor, run, glyphs);
+        }
+
+        Ok(())
+    }
+
+    fn draw_image(&self, canvas: &mut Canvas, spec: &ImageSpec, rng: &mut impl Rng) -> Result<(), RenderError> {
+        let image = self
+            .images
+            .get(&spec.image)
+            .ok_or_else(|| RenderError::Failed(format!("Image {} is not loaded", spec.image)))?;
+
+        for position in &spec.positions {
+            let scale_factor = spec.scale.sample(rng);
+            let x_offset = spec.jitter.x.sample(rng);
+            let y_offset = spec.jitter.y.sample(rng);
+
+            Self::draw_image_at_position(
+                canvas,
+                image,
+                position.x + x_offset,
+                position.y + y_offset,
+                scale_factor
+            );
+        }
+
+        Ok(())
+    }
+
+    fn draw_image_at_position(
+        canvas: &mut Canvas,
+        image: &RgbaImage,
+        x: f32,
+        y: f32,
+        scale_factor: f32,
+    ) {
+        // Calculate new dimensions
+        let new_width = (image.width() as f32 * scale_factor) as u32;
+        let new_height = (image.height() as f32 * scale_factor) as u32;
+
+        // Resize the image
+        let resized = compositing::resize(
+            image,
+            new_width,
+            new_height,
+            image::imageops::FilterType::Lanczos3,
+        );
+
+        let start_x = x as i32;
+        let start_y = y as i32;
+
+        for (sx, sy, pixel) in resized.enumerate_pixels() {
+            canvas.paint(start_x + sx as i32, start_y + sy as i32, *pixel, 1.0);
+        }
+    }
+
+    fn draw_rect(canvas: &mut Canvas, rect: &RectSpec) {
+        let (right, bottom) = (rect.x + rect.width, rect.y + rect.height);
+
+        if let Some(fill) = rect.fill {
+            canvas.fill_rect(rect.x, rect.y, right, bottom, fill.into());
+        }
+        if let Some(stroke) = rect.stroke {
+            canvas.fill_frame(rect.x, rect.y, right, bottom, rect.stroke_width, stroke.into());
+        }
+    }
+
+    /// Draws the value of `qr.source` as a QR code with its quiet zone.
+    fn draw_qr(&self, canvas: &mut Canvas, qr: &QrSpec, values: &FieldValues) -> Result<(), RenderError> {
+        let value = values.resolve(&qr.source);
+        let code = QrCode::new(value.as_bytes())
+            .map_err(|e| RenderError::InvalidValue(format!("Value cannot be encoded as a QR code: {}", e)))?;
+
+        let width = code.width();
+        let modules = (width + 2 * QR_QUIET_ZONE) as f32;
+        let module = (qr.size / modules).floor().max(1.0);
+        let (x, y) = (qr.x.round(), qr.y.round());
+
+        if let Some(background) = qr.background {
+            canvas.fill_rect(x, y, x + modules * module, y + modules * module, background.into());
+        }
+
+        let color = qr.color.unwrap_or(self.manifest.color).into();
+        for (index, module_color) in code.to_colors().into_iter().enumerate() {
+            if module_color == qrcode::Color::Dark {
+                let left = x + (index % width + QR_QUIET_ZONE) as f32 * module;
+                let top = y + (index / width + QR_QUIET_ZONE) as f32 * module;
+                canvas.fill_rect(left, top, left + module, top + module, color);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Draws a line of text with its top-left corner at (`x`, `y`). Returns the
+    /// run and the pixel box of every glyph.
+    fn draw_text_at_position(
+        canvas: &mut Canvas,
+        shaper: &Shaper,
+        text: &FittedText,
+        x: f32,
+        y: f32,
+        color: Rgba<u8>,
+    ) -> (TextRun, Vec<Bounds>) {
+        let v_metrics = shaper.v_metrics(text.scale);
+        let baseline = y + v_metrics.ascent;
+        let glyphs = shaper.layout(&text.text, text.scale, text.spacing, x, baseline);
+        let mut boxes = Vec::new();
+
+        for glyph in glyphs {
+            if let Some(bounding_box) = glyph.pixel_bounding_box() {
+                boxes.push(Bounds {
+                    min_x: bounding_box.min.x,
+                    min_y: bounding_box.min.y,
+                    max_x: bounding_box.max.x,
+                    max_y: bounding_box.max.y,
+                });
+                glyph.draw(|gx, gy, gv| {
+                    let gx = gx as i32 + bounding_box.min.x;
+                    let gy = gy as i32 + bounding_box.min.y;
+
+                    // Coverage of the pixel by the glyph acts as opacity
+                    canvas.paint(gx, gy, color, gv);
+                });
+            }
+        }
+
+        let run = TextRun {
+            text: text.text.to_string(),
+            x,
+            baseline,
+            width: shaper.measure(&text.text, text.scale, text.spacing),
+            size: text.scale.y,
+            rotation: 0.0,
+        };
+        (run, boxes)
+    }
+}
+
+/// Parts of a page of `width` x `height`, as fractions of its size, where the
+/// template's variable fields draw their text: every declared box widened by
+/// the field's jitter, placed by its layer's transform and cut into pieces of
+/// at most [`REGION_ASPECT`]. They come from the manifest alone, so they are
+/// the same for every document and say nothing about its text. Lines without
+/// a declared width are left out.
+fn hash_regions(manifest: &TemplateManifest, width: u32, height: u32) -> Vec<[f32; 4]> {
+    let (width, height) = (width as f32, height as f32);
+    let mut regions = Vec::new();
+
+    for (layer, field) in manifest.variable_fields() {
+        let jitter = field.jitter;
+        let boxes: Vec<(f32, f32, f32, f32)> = field
+            .positions
+            .iter()
+            .chain(field.flows.iter().flatten())
+            .filter_map(|anchor| {
+                let right = anchor.x + anchor.width? + jitter.x.1.max(0.0);
+                let bottom = anchor.y + anchor.height.unwrap_or(field.size.1) + jitter.y.1.max(0.0);
+                Some((anchor.x + jitter.x.0.min(0.0), anchor.y + jitter.y.0.min(0.0), right, bottom))
+            })
+            .collect();
+
+        // Layers without an explicit origin turn around the middle of what
+        // they draw, which the boxes stand in for here.
+        let drawn = boxes.iter().fold(None, |bounds: Option<Bounds>, &(left, top, right, bottom)| {
+            let (left, top, right, bottom) = (left.floor() as i32, top.floor() as i32, right.ceil() as i32, bottom.ceil() as i32);
+            Some(match bounds {
+                Some(b) => Bounds {
+                    min_x: b.min_x.min(left),
+                    min_y: b.min_y.min(top),
+                    max_x: b.max_x.max(right),
+                    max_y: b.max_y.max(bottom),
+                },
+                None => Bounds { min_x: left, min_y: top, max_x: right, max_y: bottom },
+            })
+        });
+        let placement = Placement::new(&layer.transform, drawn);
+
+        for area in boxes {
+            let (left, top, right, bottom) = layout_debug::transformed(&placement, area);
+            let pieces = ((right - left) / ((bottom - top) * REGION_ASPECT)).ceil().max(1.0);
+            let step = (right - left) / pieces;
+
+            regions.extend((0..pieces as usize).map(|i| {
+                let left = left + step * i as f32;
+                [
+                    (left / width).clamp(0.0, 1.0),
+                    (top / height).clamp(0.0, 1.0),
+                    ((left + step) / width).clamp(0.0, 1.0),
+                    (bottom / height).clamp(0.0, 1.0),
+                ]
+            }));
+        }
+    }
+
+    regions
+}
@@ -0,0 +1,229 @@
+use image::{Rgba, RgbaImage};
+use rusttype::Scale;
+use serde::Serialize;
+
+use crate::compositing::BlendMode;
+use crate::image_generator::TextPlacement;
+use crate::scene::{Bounds, Canvas, Placement};
+use crate::template::Transform;
+use crate::text::Shaper;
+
+/// Pixels a glyph may reach past an edge before it is reported. Glyph boxes
+/// include their antialiased fringe, which routinely pokes out by one pixel.
+const TOLERANCE: f32 = 1.0;
+
+const ANCHOR_COLOR: Rgba<u8> = Rgba([255, 0, 200, 255]);
+const BOX_COLOR: Rgba<u8> = Rgba([0, 170, 255, 255]);
+const ENVELOPE_COLOR: Rgba<u8> = Rgba([255, 140, 0, 255]);
+const ISSUE_COLOR: Rgba<u8> = Rgba([230, 0, 0, 255]);
+const LABEL_BACKGROUND: Rgba<u8> = Rgba([255, 255, 255, 220]);
+const LABEL_SIZE: f32 = 13.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LayoutProblem {
+    /// Glyphs are cut off by the edge of the template image.
+    OutsideImage,
+    /// Glyphs reach past the box the field's anchor declares.
+    OutsideBox,
+}
+
+/// How far, in pixels, glyphs reach past each edge.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct Overflow {
+    pub left: f32,
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+}
+
+impl Overflow {
+    fn of(glyph: (f32, f32, f32, f32), edges: (f32, f32, f32, f32)) -> Self {
+        Self {
+            left: (edges.0 - glyph.0).max(0.0),
+            top: (edges.1 - glyph.1).max(0.0),
+            right: (glyph.2 - edges.2).max(0.0),
+            bottom: (glyph.3 - edges.3).max(0.0),
+        }
+    }
+
+    fn exceeds_tolerance(&self) -> bool {
+        [self.left, self.top, self.right, self.bottom].iter().any(|edge| *edge > TOLERANCE)
+    }
+
+    fn max(self, other: Self) -> Self {
+        Self {
+            left: self.left.max(other.left),
+            top: self.top.max(other.top),
+            right: self.right.max(other.right),
+            bottom: self.bottom.max(other.bottom),
+        }
+    }
+}
+
+/// Glyphs of a text line that land where they should not.
+#[derive(Debug, Clone, Serialize)]
+pub struct LayoutIssue {
+    pub field: String,
+    pub text: String,
+    pub problem: LayoutProblem,
+    /// Number of glyphs past the edge.
+    pub glyphs: usize,
+    pub overflow: Overflow,
+    /// Page pixel boxes of the offending glyphs, as `[left, top, right, bottom]`.
+    #[serde(skip)]
+    boxes: Vec<[f32; 4]>,
+}
+
+/// Finds glyphs outside the `width` x `height` template image and outside
+/// the boxes of their anchors.
+pub fn check(layout: &[TextPlacement], width: u32, height: u32) -> Vec<LayoutIssue> {
+    let mut issues = Vec::new();
+
+    for line in layout {
+        let page = (0.0, 0.0, width as f32, height as f32);
+        let on_page: Vec<_> = line.glyphs.iter().map(|glyph| transformed(&line.placement, as_floats(*glyph))).collect();
+        issues.extend(issue(line, LayoutProblem::OutsideImage, &on_page, page));
+
+        // The box moves with the layer, so compare in canvas coordinates
+        let anchor = line.anchor;
+        if anchor.width.is_some() || anchor.height.is_some() {
+            let edges = (
+                anchor.x,
+                anchor.y,
+                anchor.width.map_or(f32::INFINITY, |w| anchor.x + w),
+                anchor.height.map_or(f32::INFINITY, |h| anchor.y + h),
+            );
+            let on_canvas: Vec<_> = line.glyphs.iter().map(|glyph| as_floats(*glyph)).collect();
+            if let Some(mut outside) = issue(line, LayoutProblem::OutsideBox, &on_canvas, edges) {
+                for glyph in &mut outside.boxes {
+                    let (left, top, right, bottom) = transformed(&line.placement, (glyph[0], glyph[1], glyph[2], glyph[3]));
+                    *glyph = [left, top, right, bottom];
+                }
+                issues.push(outside);
+            }
+        }
+    }
+
+    issues
+}
+
+fn issue(
+    line: &TextPlacement,
+    problem: LayoutProblem,
+    glyphs: &[(f32, f32, f32, f32)],
+    edges: (f32, f32, f32, f32),
+) -> Option<LayoutIssue> {
+    let outside: Vec<_> = glyphs
+        .iter()
+        .map(|glyph| (*glyph, Overflow::of(*glyph, edges)))
+        .filter(|(_, overflow)| overflow.exceeds_tolerance())
+        .collect();
+    if outside.is_empty() {
+        return None;
+    }
+
+    Some(LayoutIssue {
+        field: line.field.clone(),
+        text: line.text.clone(),
+        problem,
+        glyphs: outside.len(),
+        overflow: outside.iter().fold(Overflow::default(), |total, (_, overflow)| total.max(*overflow)),
+        boxes: outside.iter().map(|(glyph, _)| [glyph.0, glyph.1, glyph.2, glyph.3]).collect(),
+    })
+}
+
+fn as_floats(bounds: Bounds) -> (f32, f32, f32, f32) {
+    (bounds.min_x as f32, bounds.min_y as f32, bounds.max_x as f32, bounds.max_y as f32)
+}
+
+/// Axis-aligned page box around a transformed canvas box.
+pub(crate) fn transformed(placement: &Placement, (left, top, right, bottom): (f32, f32, f32, f32)) -> (f32, f32, f32, f32) {
+    let corners = [
+        placement.apply(left, top),
+        placement.apply(right, top),
+        placement.apply(left, bottom),
+        placement.apply(right, bottom),
+    ];
+    corners.iter().fold(
+        (f32::INFINITY, f32::INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+        |(min_x, min_y, max_x, max_y), (x, y)| (min_x.min(*x), min_y.min(*y), max_x.max(*x), max_y.max(*y)),
+    )
+}
+
+/// Draws the anchor, box, jitter envelope and field name of every line of
+/// `layout`, and outlines the glyphs of `issues`.
+pub fn draw_overlay(image: &mut RgbaImage, layout: &[TextPlacement], issues: &[LayoutIssue], shaper: &Shaper) {
+    let mut canvas = Canvas::new(image.width(), image.height());
+
+    for line in layout {
+        let anchor = line.anchor;
+        let ink = line.glyphs.iter().fold(None, |ink: Option<(f32, f32)>, glyph| {
+            let (right, bottom) = (glyph.max_x as f32, glyph.max_y as f32);
+            Some(ink.map_or((right, bottom), |(r, b)| (r.max(right), b.max(bottom))))
+        });
+        // Without a declared box, show the area the text actually took
+        let width = anchor.width.unwrap_or_else(|| ink.map_or(0.0, |(right, _)| (right - anchor.x).max(0.0)));
+        let height = anchor.height.unwrap_or_else(|| ink.map_or(0.0, |(_, bottom)| (bottom - anchor.y).max(0.0)));
+
+        let jitter = line.jitter;
+        draw_quad(
+            &mut canvas,
+            &line.placement,
+            (anchor.x + jitter.x.0, anchor.y + jitter.y.0, anchor.x + jitter.x.1 + width, anchor.y + jitter.y.1 + height),
+            ENVELOPE_COLOR,
+        );
+        draw_quad(&mut canvas, &line.placement, (anchor.x, anchor.y, anchor.x + width, anchor.y + height), BOX_COLOR);
+
+        let (x, y) = line.placement.apply(anchor.x, anchor.y);
+        canvas.draw_line((x - 6.0, y), (x + 6.0, y), 2.0, ANCHOR_COLOR);
+        canvas.draw_line((x, y - 6.0), (x, y + 6.0), 2.0, ANCHOR_COLOR);
+    }
+
+    for issue in issues {
+        for glyph in &issue.boxes {
+            draw_quad(&mut canvas, &identity(), (glyph[0], glyph[1], glyph[2], glyph[3]), ISSUE_COLOR);
+        }
+    }
+
+    // Labels last so lines never cross them
+    for line in layout {
+        let (x, y) = line.placement.apply(line.anchor.x, line.anchor.y);
+        draw_label(&mut canvas, shaper, &line.field, x + 4.0, y - LABEL_SIZE - 4.0);
+    }
+
+    identity().composite(image, &canvas, 1.0, BlendMode::Normal);
+}
+
+fn identity() -> Placement {
+    Placement::new(&Transform::default(), None)
+}
+
+/// Outlines a canvas rectangle as it lands on the page.
+fn draw_quad(canvas: &mut Canvas, placement: &Placement, (left, top, right, bottom): (f32, f32, f32, f32), color: Rgba<u8>) {
+    let corners = [
+        placement.apply(left, top),
+        placement.apply(right, top),
+        placement.apply(right, bottom),
+        placement.apply(left, bottom),
+    ];
+    for (index, from) in corners.iter().enumerate() {
+        canvas.draw_line(*from, corners[(index + 1) % corners.len()], 1.0, color);
+    }
+}
+
+/// Writes `text` on a light plate with its top-left corner at (`x`, `y`).
+fn draw_label(canvas: &mut Canvas, shaper: &Shaper, text: &str, x: f32, y: f32) {
+    let scale = Scale::uniform(LABEL_SIZE);
+    let v_metrics = shaper.v_metrics(scale);
+    let width = shaper.measure(text, scale, 0.0);
+
+    canvas.fill_rect(x - 2.0, y - 1.0, x + width + 2.0, y + v_metrics.ascent - v_metrics.descent + 1.0, LABEL_BACKGROUND);
+    for glyph in shaper.layout(text, scale, 0.0, x, y + v_metrics.ascent) {
+        if let Some(bounding_box) = glyph.pixel_bounding_box() {
+            glyph.draw(|gx, gy, coverage| {
+                canvas.paint(gx as i32 + bounding_box.min.x, gy as i32 + bounding_box.min.y, ANCHOR_COLOR, coverage);
+            });
+        }
+    }
+}
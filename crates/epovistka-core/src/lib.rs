@@ -0,0 +1,20 @@
+//! Document renderer behind the epovistka server: template manifests, the
+//! layer compositor, text shaping and the output encoders. Load templates
+//! with [`template_registry::TemplateRegistry`] and render them with
+//! [`image_generator::ImageGenerator::generate_image`].
+
+pub mod compositing;
+pub mod error;
+pub mod fingerprint;
+pub mod fonts;
+pub mod image_generator;
+pub mod layout_debug;
+pub mod locale;
+pub mod output;
+pub mod pdf;
+pub mod perceptual_hash;
+pub mod provenance;
+pub mod scene;
+pub mod template;
+pub mod template_registry;
+pub mod text;
@@ -0,0 +1,188 @@
+use chrono::{Datelike, NaiveDate, NaiveTime, Timelike};
+use serde::{Deserialize, Serialize};
+
+/// Languages documents can be rendered in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LocaleId {
+    #[default]
+    Uk,
+    En,
+    Pl,
+    De,
+}
+
+/// Month names, number and date conventions of one language.
+///
+/// Date and time patterns use CLDR-style letters: `d`/`dd` day, `M`/`MM`
+/// month number, `MMMM` month name in the form used inside a date (genitive
+/// in Slavic languages), `LLLL` standalone (nominative) month name, `yy`/`yyyy`
+/// year, `H`/`HH` 24-hour, `h`/`hh` 12-hour, `mm` minutes and `a` AM/PM.
+/// Text in single quotes is copied verbatim.
+#[derive(Debug)]
+pub struct Locale {
+    months_nominative: [&'static str; 12],
+    months_genitive: [&'static str; 12],
+    group_separator: &'static str,
+    pub date_pattern: &'static str,
+    pub time_pattern: &'static str,
+}
+
+static UK: Locale = Locale {
+    months_nominative: [
+        "січень", "лютий", "березень", "квітень", "травень", "червень",
+        "липень", "серпень", "вересень", "жовтень", "листопад", "грудень",
+    ],
+    months_genitive: [
+        "січня", "лютого", "березня", "квітня", "травня", "червня",
+        "липня", "серпня", "вересня", "жовтня", "листопада", "грудня",
+    ],
+    group_separator: " ",
+    date_pattern: "d MMMM yyyy",
+    time_pattern: "HH:mm",
+};
+
+static EN: Locale = Locale {
+    months_nominative: [
+        "January", "February", "March", "April", "May", "June",
+        "July", "August", "September", "October", "November", "December",
+    ],
+    months_genitive: [
+        "January", "February", "March", "April", "May", "June",
+        "July", "August", "September", "October", "November", "December",
+    ],
+    group_separator: ",",
+    date_pattern: "MMMM d, yyyy",
+    time_pattern: "h:mm a",
+};
+
+static PL: Locale = Locale {
+    months_nominative: [
+        "styczeń", "luty", "marzec", "kwiecień", "maj", "czerwiec",
+        "lipiec", "sierpień", "wrzesień", "październik", "listopad", "grudzień",
+    ],
+    months_genitive: [
+        "stycznia", "lutego", "marca", "kwietnia", "maja", "czerwca",
+        "lipca", "sierpnia", "września", "października", "listopada", "grudnia",
+    ],
+    group_separator: " ",
+    date_pattern: "d MMMM yyyy",
+    time_pattern: "HH:mm",
+};
+
+static DE: Locale = Locale {
+    months_nominative: [
+        "Januar", "Februar", "März", "April", "Mai", "Juni",
+        "Juli", "August", "September", "Oktober", "November", "Dezember",
+    ],
+    months_genitive: [
+        "Januar", "Februar", "März", "April", "Mai", "Juni",
+        "Juli", "August", "September", "Oktober", "November", "Dezember",
+    ],
+    group_separator: ".",
+    date_pattern: "d. MMMM yyyy",
+    time_pattern: "HH:mm",
+};
+
+impl Locale {
+    pub fn get(id: LocaleId) -> &'static Locale {
+        match id {
+            LocaleId::Uk => &UK,
+            LocaleId::En => &EN,
+            LocaleId::Pl => &PL,
+            LocaleId::De => &DE,
+        }
+    }
+
+    /// Month name as used inside a date, e.g. "5 травня".
+    pub fn month_genitive(&self, month: u32) -> &'static str {
+        self.months_genitive[(month.clamp(1, 12) - 1) as usize]
+    }
+
+    /// Month name on its own, e.g. "травень".
+    pub fn month_nominative(&self, month: u32) -> &'static str {
+        self.months_nominative[(month.clamp(1, 12) - 1) as usize]
+    }
+
+    /// Formats an integer, optionally with the locale's thousands separator.
+    pub fn format_number(&self, number: u64, grouped: bool) -> String {
+        let digits = number.to_string();
+        if !grouped {
+            return digits;
+        }
+
+        let mut result = String::with_capacity(digits.len() * 2);
+        for (i, digit) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i).is_multiple_of(3) {
+                result.push_str(self.group_separator);
+            }
+            result.push(digit);
+        }
+        result
+    }
+
+    pub fn format_date(&self, date: NaiveDate, pattern: &str) -> String {
+        self.format(pattern, Some(date), None)
+    }
+
+    pub fn format_time(&self, time: NaiveTime, pattern: &str) -> String {
+        self.format(pattern, None, Some(time))
+    }
+
+    fn format(&self, pattern: &str, date: Option<NaiveDate>, time: Option<NaiveTime>) -> String {
+        let mut result = String::new();
+        let mut chars = pattern.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            if c == '\'' {
+                for literal in chars.by_ref() {
+                    if literal == '\'' {
+                        break;
+                    }
+                    result.push(literal);
+                }
+                continue;
+            }
+
+            if !c.is_ascii_alphabetic() {
+                result.push(c);
+                continue;
+            }
+
+            let mut count = 1;
+            while chars.peek() == Some(&c) {
+                chars.next();
+                count += 1;
+            }
+
+            match self.field(c, count, date, time) {
+                Some(value) => result.push_str(&value),
+                None => result.extend(std::iter::repeat_n(c, count)),
+            }
+        }
+
+        result
+    }
+
+    fn field(&self, letter: char, count: usize, date: Option<NaiveDate>, time: Option<NaiveTime>) -> Option<String> {
+        let value = match (letter, count) {
+            ('d', 1) => date?.day().to_string(),
+            ('d', 2) => format!("{:02}", date?.day()),
+            ('M', 1) => date?.month().to_string(),
+            ('M', 2) => format!("{:02}", date?.month()),
+            ('M', 4) => self.month_genitive(date?.month()).to_string(),
+            ('L', 4) => self.month_nominative(date?.month()).to_string(),
+            ('y', 2) => format!("{:02}", date?.year().rem_euclid(100)),
+            ('y', 4) => date?.year().to_string(),
+            ('H', 1) => time?.hour().to_string(),
+            ('H', 2) => format!("{:02}", time?.hour()),
+            ('h', 1) => time?.hour12().1.to_string(),
+            ('h', 2) => format!("{:02}", time?.hour12().1),
+            ('m', 2) => format!("{:02}", time?.minute()),
+            ('a', 1) => if time?.hour12().0 { "PM" } else { "AM" }.to_string(),
+            _ => return None,
+        };
+
+        Some(value)
+    }
+}
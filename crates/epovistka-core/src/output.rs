@@ -0,0 +1,548 @@
+use image::{ExtendedColorType, ImageEncoder, Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::image_generator::RenderedPage;
+use crate::compositing::{self, BlendMode};
+use crate::pdf;
+use crate::provenance::Provenance;
+use crate::template::{PageSpec, PaperSize};
+
+const DEFAULT_JPEG_QUALITY: u8 = 85;
+const DEFAULT_WEBP_QUALITY: u8 = 80;
+const DEFAULT_AVIF_QUALITY: u8 = 70;
+/// rav1e speed preset: 1 is slowest/best, 10 fastest.
+const AVIF_SPEED: u8 = 8;
+const WHITE: Rgba<u8> = Rgba([255, 255, 255, 255]);
+/// Identifier that starts the XMP packet in a JPEG APP1 segment.
+const JPEG_XMP_SIGNATURE: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+/// Content type of the item that holds the XMP packet in a HEIF file such as AVIF.
+const HEIF_XMP_CONTENT_TYPE: &[u8] = b"application/rdf+xml";
+
+/// Encodings a rendered document can be returned in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputFormat {
+    #[default]
+    Png,
+    Jpeg,
+    /// Lossy WebP.
+    Webp,
+    WebpLossless,
+    Avif,
+    /// The page placed at the template's physical size, with a selectable text layer.
+    Pdf,
+}
+
+impl OutputFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            OutputFormat::Png => "image/png",
+            OutputFormat::Jpeg => "image/jpeg",
+            OutputFormat::Webp | OutputFormat::WebpLossless => "image/webp",
+            OutputFormat::Avif => "image/avif",
+            OutputFormat::Pdf => "application/pdf",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Png => "png",
+            OutputFormat::Jpeg => "jpg",
+            OutputFormat::Webp | OutputFormat::WebpLossless => "webp",
+            OutputFormat::Avif => "avif",
+            OutputFormat::Pdf => "pdf",
+        }
+    }
+
+    fn from_media_type(media_type: &str) -> Option<Self> {
+        match media_type {
+            "image/png" => Some(OutputFormat::Png),
+            "image/jpeg" | "image/jpg" => Some(OutputFormat::Jpeg),
+            "image/webp" => Some(OutputFormat::Webp),
+            "image/avif" => Some(OutputFormat::Avif),
+            "application/pdf" => Some(OutputFormat::Pdf),
+            "image/*" | "*/*" => Some(OutputFormat::default()),
+            _ => None,
+        }
+    }
+
+    /// Format a file name extension stands for. Lossless WebP shares its
+    /// extension with lossy WebP, so it is never guessed.
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "png" => Some(OutputFormat::Png),
+            "jpg" | "jpeg" => Some(OutputFormat::Jpeg),
+            "webp" => Some(OutputFormat::Webp),
+            "avif" => Some(OutputFormat::Avif),
+            "pdf" => Some(OutputFormat::Pdf),
+            _ => None,
+        }
+    }
+
+    /// Picks the format an `Accept` header prefers most, honouring q-values.
+    /// Falls back to PNG when nothing supported is acceptable.
+    pub fn negotiate(accept: &str) -> Self {
+        let mut best: Option<(f32, bool, OutputFormat)> = None;
+
+        for entry in accept.split(',') {
+            let mut parts = entry.split(';');
+            let media_type = parts.next().unwrap_or_default().trim().to_ascii_lowercase();
+            let quality = parts
+                .filter_map(|param| param.trim().strip_prefix("q="))
+                .find_map(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+
+            let Some(format) = Self::from_media_type(&media_type) else {
+                continue;
+            };
+            if quality <= 0.0 {
+                continue;
+            }
+
+            // Wildcards lose ties against explicit types; earlier entries win otherwise.
+            let explicit = !media_type.ends_with("/*");
+            if best.is_none_or(|(q, e, _)| (quality, explicit) > (q, e)) {
+                best = Some((quality, explicit, format));
+            }
+        }
+
+        best.map(|(_, _, format)| format).unwrap_or_default()
+    }
+}
+
+/// Requested encoding plus its quality setting (1-100) for lossy formats and
+/// the paper size for PDF.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OutputOptions {
+    pub format: OutputFormat,
+    pub quality: Option<u8>,
+    pub paper: Option<PaperSize>,
+}
+
+/// Encodes the page and embeds `provenance`: as tEXt/iTXt chunks and XMP in
+/// PNG, XMP in JPEG, WebP and AVIF, and the document information dictionary in
+/// PDF. AVIF is returned without XMP if the encoder's layout is not recognised.
+pub fn encode(
+    page: &RenderedPage,
+    spec: &PageSpec,
+    options: OutputOptions,
+    provenance: &Provenance,
+) -> Result<Vec<u8>, String> {
+    let image = &page.image;
+    let mut bytes = Vec::new();
+    let (width, height) = image.dimensions();
+
+    match options.format {
+        OutputFormat::Png => {
+            bytes = encode_png(image, provenance).map_err(|e| format!("Failed to encode PNG: {}", e))?;
+        }
+        OutputFormat::Jpeg => {
+            let quality = options.quality.unwrap_or(DEFAULT_JPEG_QUALITY);
+            let rgb = flatten(image);
+            image::codecs::jpeg::JpegEncoder::new_with_quality(&mut bytes, quality)
+                .write_image(&rgb, width, height, ExtendedColorType::Rgb8)
+                .map_err(|e| format!("Failed to encode JPEG: {}", e))?;
+            bytes = embed_jpeg_xmp(bytes, &provenance.xmp())?;
+        }
+        OutputFormat::Webp => {
+            let quality = options.quality.unwrap_or(DEFAULT_WEBP_QUALITY);
+            let encoded = webp::Encoder::from_rgba(image.as_raw(), width, height).encode(quality as f32);
+            bytes = embed_webp_xmp(encoded.to_vec(), width, height, &provenance.xmp())?;
+        }
+        OutputFormat::WebpLossless => {
+            image::codecs::webp::WebPEncoder::new_lossless(&mut bytes)
+                .write_image(image, width, height, ExtendedColorType::Rgba8)
+                .map_err(|e| format!("Failed to encode WebP: {}", e))?;
+            bytes = embed_webp_xmp(bytes, width, height, &provenance.xmp())?;
+        }
+        OutputFormat::Avif => {
+            let quality = options.quality.unwrap_or(DEFAULT_AVIF_QUALITY);
+            image::codecs::avif::AvifEncoder::new_with_speed_quality(&mut bytes, AVIF_SPEED, quality)
+                .write_image(image, width, height, ExtendedColorType::Rgba8)
+                .map_err(|e| format!("Failed to encode AVIF: {}", e))?;
+            // Metadata is a courtesy, an encoder layout the patcher does not
+            // know must not cost the caller the image.
+            match embed_avif_xmp(&bytes, &provenance.xmp()) {
+                Ok(embedded) => bytes = embedded,
+                Err(e) => warn!("Leaving the XMP packet out of AVIF output: {}", e),
+            }
+        }
+        OutputFormat::Pdf => {
+            let spec = PageSpec { size: options.paper.unwrap_or(spec.size), ..*spec };
+            bytes = pdf::write(page, &spec, provenance);
+        }
+    }
+
+    Ok(bytes)
+}
+
+fn encode_png(image: &RgbaImage, provenance: &Provenance) -> Result<Vec<u8>, png::EncodingError> {
+    let mut bytes = Vec::new();
+    let mut encoder = png::Encoder::new(&mut bytes, image.width(), image.height());
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_compression(png::Compression::Balanced);
+
+    encoder.add_text_chunk("Software".to_string(), provenance.software())?;
+    encoder.add_text_chunk("Description".to_string(), crate::provenance::NOTICE.to_string())?;
+    encoder.add_text_chunk("Creation Time".to_string(), provenance.timestamp())?;
+    encoder.add_text_chunk("Render ID".to_string(), provenance.render_id.clone())?;
+    // Template ids may be non-Latin, which tEXt cannot hold.
+    encoder.add_itxt_chunk("Template".to_string(), provenance.template.clone())?;
+    encoder.add_itxt_chunk("XML:com.adobe.xmp".to_string(), provenance.xmp())?;
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(image.as_raw())?;
+    writer.finish()?;
+
+    Ok(bytes)
+}
+
+/// Inserts an XMP APP1 segment after the JFIF header of a baseline JPEG.
+fn embed_jpeg_xmp(jpeg: Vec<u8>, xmp: &str) -> Result<Vec<u8>, String> {
+    if !jpeg.starts_with(&[0xFF, 0xD8]) {
+        return Err("Encoded JPEG has no start of image marker".to_string());
+    }
+
+    // Keep JFIF APP0 first, as the JFIF specification requires.
+    let mut insert_at = 2;
+    if jpeg.get(2..4) == Some(&[0xFF, 0xE0]) {
+        let length = u16::from_be_bytes([jpeg[4], jpeg[5]]) as usize;
+        insert_at = 4 + length;
+    }
+
+    let length = 2 + JPEG_XMP_SIGNATURE.len() + xmp.len();
+    let length = u16::try_from(length).map_err(|_| "XMP packet is too large for a JPEG segment".to_string())?;
+
+    let mut bytes = Vec::with_capacity(jpeg.len() + length as usize + 2);
+    bytes.extend_from_slice(&jpeg[..insert_at]);
+    bytes.extend_from_slice(&[0xFF, 0xE1]);
+    bytes.extend_from_slice(&length.to_be_bytes());
+    bytes.extend_from_slice(JPEG_XMP_SIGNATURE);
+    bytes.extend_from_slice(xmp.as_bytes());
+    bytes.extend_from_slice(&jpeg[insert_at..]);
+
+    Ok(bytes)
+}
+
+/// Adds an `XMP ` chunk to a WebP file, converting a simple file to the
+/// extended format (with a `VP8X` header chunk) if needed.
+fn embed_webp_xmp(webp: Vec<u8>, width: u32, height: u32, xmp: &str) -> Result<Vec<u8>, String> {
+    const XMP_FLAG: u8 = 0x04;
+    const ALPHA_FLAG: u8 = 0x10;
+
+    if webp.len() < 20 || &webp[0..4] != b"RIFF" || &webp[8..12] != b"WEBP" {
+        return Err("Encoded WebP has no RIFF header".to_string());
+    }
+
+    let mut bytes = Vec::with_capacity(webp.len() + xmp.len() + 32);
+    bytes.extend_from_slice(&webp[..12]);
+
+    if &webp[12..16] == b"VP8X" {
+        bytes.extend_from_slice(&webp[12..]);
+        bytes[20] |= XMP_FLAG;
+    } else {
+        // A lossless bitstream records whether it uses alpha in its header.
+        let alpha = &webp[12..16] == b"VP8L"
+            && webp.len() >= 25
+            && u32::from_le_bytes([webp[21], webp[22], webp[23], webp[24]]) >> 28 & 1 == 1;
+
+        let mut header = [0u8; 10];
+        header[0] = XMP_FLAG | if alpha { ALPHA_FLAG } else { 0 };
+        header[4..7].copy_from_slice(&(width - 1).to_le_bytes()[..3]);
+        header[7..10].copy_from_slice(&(height - 1).to_le_bytes()[..3]);
+
+        push_riff_chunk(&mut bytes, b"VP8X", &header);
+        bytes.extend_from_slice(&webp[12..]);
+    }
+
+    push_riff_chunk(&mut bytes, b"XMP ", xmp.as_bytes());
+
+    let riff_size = u32::try_from(bytes.len() - 8).map_err(|_| "WebP file is too large".to_string())?;
+    bytes[4..8].copy_from_slice(&riff_size.to_le_bytes());
+
+    Ok(bytes)
+}
+
+fn push_riff_chunk(bytes: &mut Vec<u8>, fourcc: &[u8; 4], data: &[u8]) {
+    bytes.extend_from_slice(fourcc);
+    bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(data);
+    // Chunks are padded to an even size
+    if data.len() % 2 == 1 {
+        bytes.push(0);
+    }
+}
+
+/// Adds the XMP packet to an AVIF file as a `mime` item that describes the
+/// primary image, stored in an `mdat` box of its own at the end of the file.
+///
+/// The encoder has no way to write XMP itself. This handles the layout it
+/// produces: `meta` ahead of `mdat`, and a version 0 `iloc` with 32-bit
+/// offsets and lengths and no base offset.
+fn embed_avif_xmp(avif: &[u8], xmp: &str) -> Result<Vec<u8>, String> {
+    let invalid = |what: &str| format!("Encoded AVIF has {}", what);
+
+    let top = iso_boxes(avif).ok_or_else(|| invalid("a malformed box"))?;
+    let meta = top.iter().find(|b| &b.fourcc == b"meta").ok_or_else(|| invalid("no meta box"))?;
+    // `meta` is a full box: version and flags come before its children.
+    let meta_header = meta.body.get(..4).ok_or_else(|| invalid("an empty meta box"))?;
+    let meta_children = &meta.body[4..];
+    let children = iso_boxes(meta_children).ok_or_else(|| invalid("a malformed meta box"))?;
+    let child = |fourcc: &[u8; 4]| children.iter().find(|b| &b.fourcc == fourcc);
+
+    let primary = child(b"pitm")
+        .and_then(|pitm| read_u16(pitm.body, 4))
+        .ok_or_else(|| invalid("no primary item"))?;
+    let iinf = child(b"iinf").ok_or_else(|| invalid("no iinf box"))?;
+    if child(b"iloc").is_none() {
+        return Err(invalid("no iloc box"));
+    }
+    // Version 0 references use 16-bit item ids, like the `cdsc` added below.
+    let iref = child(b"iref");
+    if iref.is_some_and(|iref| iref.body.first() != Some(&0)) {
+        return Err(invalid("an unsupported iref box"));
+    }
+
+    let item = item_ids(iinf.body)
+        .and_then(|ids| ids.into_iter().max())
+        .and_then(|id| id.checked_add(1))
+        .ok_or_else(|| invalid("an unsupported iinf box"))?;
+    let xmp_len = u32::try_from(xmp.len()).map_err(|_| "XMP packet is too large".to_string())?;
+
+    // infe version 2: id, protection index, type, empty name, content type
+    let mut infe = vec![2, 0, 0, 0];
+    infe.extend_from_slice(&item.to_be_bytes());
+    infe.extend_from_slice(&[0, 0]);
+    infe.extend_from_slice(b"mime\0");
+    infe.extend_from_slice(HEIF_XMP_CONTENT_TYPE);
+    infe.push(0);
+    let infe = iso_box(b"infe", &infe);
+
+    // The XMP item describes (`cdsc`) the primary image.
+    let mut cdsc = item.to_be_bytes().to_vec();
+    cdsc.extend_from_slice(&1u16.to_be_bytes());
+    cdsc.extend_from_slice(&primary.to_be_bytes());
+    let cdsc = iso_box(b"cdsc", &cdsc);
+
+    // Growth of `meta`, which moves everything stored after it.
+    const ILOC_ITEM_LEN: usize = 14;
+    let new_iref_len = if iref.is_none() { 12 } else { 0 };
+    let shift = infe.len() + ILOC_ITEM_LEN + cdsc.len() + new_iref_len;
+    let xmp_offset = avif.len() + shift + 8;
+
+    let mut body = meta_header.to_vec();
+    for b in &children {
+        match &b.fourcc {
+            b"iinf" => {
+                let count = read_u16(b.body, 4).ok_or_else(|| invalid("an unsupported iinf box"))?;
+                let mut iinf = b.body.to_vec();
+                iinf[4..6].copy_from_slice(&(count + 1).to_be_bytes());
+                iinf.extend_from_slice(&infe);
+                body.extend_from_slice(&iso_box(b"iinf", &iinf));
+                if iref.is_none() {
+                    let mut iref = vec![0, 0, 0, 0];
+                    iref.extend_from_slice(&cdsc);
+                    body.extend_from_slice(&iso_box(b"iref", &iref));
+                }
+            }
+            b"iloc" => {
+                let iloc = shift_iloc(b.body, meta.end, shift, (item, xmp_offset, xmp_len))
+                    .ok_or_else(|| invalid("an unsupported iloc box"))?;
+                body.extend_from_slice(&iso_box(b"iloc", &iloc));
+            }
+            b"iref" => {
+                let mut iref = b.body.to_vec();
+                iref.extend_from_slice(&cdsc);
+                body.extend_from_slice(&iso_box(b"iref", &iref));
+            }
+            _ => body.extend_from_slice(&meta_children[b.start..b.end]),
+        }
+    }
+
+    let mut bytes = Vec::with_capacity(avif.len() + shift + xmp.len() + 8);
+    bytes.extend_from_slice(&avif[..meta.start]);
+    bytes.extend_from_slice(&iso_box(b"meta", &body));
+    bytes.extend_from_slice(&avif[meta.end..]);
+    bytes.extend_from_slice(&iso_box(b"mdat", xmp.as_bytes()));
+    debug_assert_eq!(bytes.len(), xmp_offset + xmp.len());
+
+    Ok(bytes)
+}
+
+/// A box of an ISO base media file, with its position in the data it was read from.
+struct IsoBox<'a> {
+    fourcc: [u8; 4],
+    body: &'a [u8],
+    start: usize,
+    end: usize,
+}
+
+/// Splits `data` into consecutive boxes. Only 32-bit sizes are supported.
+fn iso_boxes(data: &[u8]) -> Option<Vec<IsoBox<'_>>> {
+    let mut boxes = Vec::new();
+    let mut start = 0;
+    while start < data.len() {
+        let size = read_u32(data, start)? as usize;
+        if size < 8 {
+            return None;
+        }
+        let end = start.checked_add(size).filter(|&end| end <= data.len())?;
+        boxes.push(IsoBox { fourcc: data[start + 4..start + 8].try_into().ok()?, body: &data[start + 8..end], start, end });
+        start = end;
+    }
+    Some(boxes)
+}
+
+fn iso_box(fourcc: &[u8; 4], body: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(body.len() + 8);
+    bytes.extend_from_slice(&(body.len() as u32 + 8).to_be_bytes());
+    bytes.extend_from_slice(fourcc);
+    bytes.extend_from_slice(body);
+    bytes
+}
+
+/// Ids of the entries of an `iinf` box (version 0, `infe` versions 2 and 3).
+fn item_ids(iinf: &[u8]) -> Option<Vec<u16>> {
+    if iinf.first() != Some(&0) {
+        return None;
+    }
+    iso_boxes(iinf.get(6..)?)?
+        .iter()
+        .map(|infe| match infe.body.first()? {
+            2 => read_u16(infe.body, 4),
+            3 => read_u32(infe.body, 4).and_then(|id| u16::try_from(id).ok()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Copy of a version 0 `iloc` body with offsets at or past `from` moved by
+/// `shift` and an item appended with a single extent of (`offset`, `length`).
+fn shift_iloc(iloc: &[u8], from: usize, shift: usize, (item, offset, length): (u16, usize, u32)) -> Option<Vec<u8>> {
+    // Version 0, 32-bit offsets and lengths, no base offset
+    if iloc.get(..6)? != [0, 0, 0, 0, 0x44, 0x00] {
+        return None;
+    }
+    let count = read_u16(iloc, 6)?;
+
+    let mut bytes = iloc[..8].to_vec();
+    bytes[6..8].copy_from_slice(&(count + 1).to_be_bytes());
+    let mut at = 8;
+    for _ in 0..count {
+        let extents = read_u16(iloc, at + 4)?;
+        bytes.extend_from_slice(iloc.get(at..at + 6)?);
+        at += 6;
+        for _ in 0..extents {
+            let mut extent_offset = read_u32(iloc, at)? as usize;
+            if extent_offset >= from {
+                extent_offset += shift;
+            }
+            bytes.extend_from_slice(&u32::try_from(extent_offset).ok()?.to_be_bytes());
+            bytes.extend_from_slice(iloc.get(at + 4..at + 8)?);
+            at += 8;
+        }
+    }
+    if at != iloc.len() {
+        return None;
+    }
+
+    bytes.extend_from_slice(&item.to_be_bytes());
+    bytes.extend_from_slice(&[0, 0, 0, 1]);
+    bytes.extend_from_slice(&u32::try_from(offset).ok()?.to_be_bytes());
+    bytes.extend_from_slice(&length.to_be_bytes());
+    Some(bytes)
+}
+
+fn read_u16(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn read_u32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+/// Composites the image over white and drops alpha, for formats without transparency.
+pub fn flatten(image: &RgbaImage) -> Vec<u8> {
+    image
+        .pixels()
+        .flat_map(|&pixel| {
+            let Rgba([r, g, b, _]) = compositing::composite(WHITE, pixel, 1.0, BlendMode::Normal);
+            [r, g, b]
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Bytes each `iloc` item points at, by item id.
+    fn avif_items(avif: &[u8]) -> Vec<(u16, Vec<u8>)> {
+        let top = iso_boxes(avif).unwrap();
+        let meta = top.iter().find(|b| &b.fourcc == b"meta").unwrap();
+        let children = iso_boxes(&meta.body[4..]).unwrap();
+        let iloc = children.iter().find(|b| &b.fourcc == b"iloc").unwrap().body;
+
+        let mut items = Vec::new();
+        let mut at = 8;
+        for _ in 0..read_u16(iloc, 6).unwrap() {
+            let id = read_u16(iloc, at).unwrap();
+            assert_eq!(read_u16(iloc, at + 4), Some(1));
+            let offset = read_u32(iloc, at + 6).unwrap() as usize;
+            let length = read_u32(iloc, at + 10).unwrap() as usize;
+            items.push((id, avif[offset..offset + length].to_vec()));
+            at += 14;
+        }
+        items
+    }
+
+    #[test]
+    fn avif_xmp_is_added_as_an_item_without_moving_the_image() {
+        // An opaque image has no `iref`; a translucent one links its alpha plane through one.
+        for alpha in [255, 128] {
+            let image = RgbaImage::from_pixel(16, 16, Rgba([200, 40, 40, alpha]));
+            let mut avif = Vec::new();
+            image::codecs::avif::AvifEncoder::new_with_speed_quality(&mut avif, AVIF_SPEED, DEFAULT_AVIF_QUALITY)
+                .write_image(&image, 16, 16, ExtendedColorType::Rgba8)
+                .unwrap();
+            assert_eq!(avif.windows(4).any(|w| w == b"iref"), alpha < 255);
+            let xmp = "<x:xmpmeta>test</x:xmpmeta>";
+
+            let embedded = embed_avif_xmp(&avif, xmp).unwrap();
+
+            let before = avif_items(&avif);
+            let after = avif_items(&embedded);
+            assert_eq!(after.len(), before.len() + 1);
+            assert_eq!(after[..before.len()], before[..]);
+
+            let (id, data) = after.last().unwrap();
+            assert_eq!(*id as usize, before.len() + 1);
+            assert_eq!(data, xmp.as_bytes());
+            assert!(embedded.windows(HEIF_XMP_CONTENT_TYPE.len()).any(|w| w == HEIF_XMP_CONTENT_TYPE));
+            assert!(embedded.windows(4).any(|w| w == b"cdsc"));
+        }
+    }
+
+    #[test]
+    fn negotiate_breaks_q_value_ties() {
+        let cases = [
+            ("image/webp, image/jpeg", OutputFormat::Webp),
+            ("image/jpeg;q=0.8, application/pdf;q=0.8", OutputFormat::Jpeg),
+            ("image/*, image/jpeg", OutputFormat::Jpeg),
+            ("*/*;q=0.8, image/avif;q=0.8", OutputFormat::Avif),
+            ("image/*;q=0.9, image/jpeg;q=0.5", OutputFormat::Png),
+            ("image/webp;q=0.5, image/avif;q=0.8", OutputFormat::Avif),
+            ("image/avif;q=0, image/jpeg;q=0.1", OutputFormat::Jpeg),
+            ("Image/WEBP", OutputFormat::Webp),
+            ("text/html, application/json", OutputFormat::Png),
+            ("image/avif;q=0", OutputFormat::Png),
+            ("", OutputFormat::Png),
+        ];
+
+        for (accept, expected) in cases {
+            assert_eq!(OutputFormat::negotiate(accept), expected, "Accept: {}", accept);
+        }
+    }
+}
@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+
+use miniz_oxide::deflate::compress_to_vec_zlib;
+use chrono::{Datelike, Timelike};
+use pdf_writer::types::{SystemInfo, TextRenderingMode, UnicodeCmap};
+use pdf_writer::{Content, Date, Filter, Finish, Name, Pdf, Rect, Ref, Str, TextStr};
+
+use crate::image_generator::{RenderedPage, TextRun};
+use crate::output;
+use crate::provenance::{self, Provenance};
+use crate::template::PageSpec;
+
+const POINTS_PER_MM: f32 = 72.0 / 25.4;
+/// Advance of every glyph of the text layer font, in thousandths of the font size.
+const GLYPH_WIDTH: f32 = 500.0;
+/// Single-byte font encodings leave code 0 unused, so each font covers 255 characters.
+const CODES_PER_FONT: usize = 255;
+
+/// Writes a single-page PDF with the rendered image at its declared physical
+/// size and the drawn text as an invisible, selectable layer on top.
+///
+/// The text layer uses a glyphless Type 3 font: every character has the same
+/// width and an empty outline, and each line is stretched horizontally to the
+/// width it occupies in the image, so selections line up with the raster.
+///
+/// `provenance` goes into the document information dictionary.
+pub fn write(page: &RenderedPage, spec: &PageSpec, provenance: &Provenance) -> Vec<u8> {
+    let (paper_width, paper_height) = spec.size.dimensions_mm();
+    let margins = spec.margins;
+    let available_width = paper_width - margins.left - margins.right;
+    let available_height = paper_height - margins.top - margins.bottom;
+
+    // Millimetres per image pixel at the declared resolution, reduced if the
+    // image does not fit between the margins.
+    let (width_px, height_px) = page.image.dimensions();
+    let mm_per_px = (25.4 / spec.dpi)
+        .min(available_width / width_px as f32)
+        .min(available_height / height_px as f32);
+    let image_width = width_px as f32 * mm_per_px;
+    let image_height = height_px as f32 * mm_per_px;
+    let left = margins.left + (available_width - image_width) / 2.0;
+    let top = margins.top;
+
+    // Maps image pixel coordinates to PDF points (origin at the bottom left).
+    let scale = mm_per_px * POINTS_PER_MM;
+    let to_pdf = |x: f32, y: f32| ((left * POINTS_PER_MM) + x * scale, (paper_height - top) * POINTS_PER_MM - y * scale);
+
+    let mut alloc = Ref::new(1);
+    let catalog_id = alloc.bump();
+    let page_tree_id = alloc.bump();
+    let page_id = alloc.bump();
+    let content_id = alloc.bump();
+    let image_id = alloc.bump();
+    let glyph_id = alloc.bump();
+    let info_id = alloc.bump();
+
+    let mut pdf = Pdf::new();
+    pdf.catalog(catalog_id).pages(page_tree_id);
+    pdf.pages(page_tree_id).kids([page_id]).count(1);
+
+    let rgb = compress_to_vec_zlib(&output::flatten(&page.image), 6);
+    let mut image = pdf.image_xobject(image_id, &rgb);
+    image.filter(Filter::FlateDecode);
+    image.width(width_px as i32);
+    image.height(height_px as i32);
+    image.color_space().device_rgb();
+    image.bits_per_component(8);
+    image.finish();
+
+    let layer = TextLayer::new(&page.text);
+    pdf.stream(glyph_id, format!("{} 0 d0", GLYPH_WIDTH).as_bytes());
+
+    let mut font_ids = Vec::new();
+    for chars in layer.fonts() {
+        let font_id = alloc.bump();
+        let cmap_id = alloc.bump();
+        font_ids.push(font_id);
+
+        let names: Vec<String> = (1..=chars.len()).map(|code| format!("g{}", code)).collect();
+        let mut font = pdf.type3_font(font_id);
+        font.bbox(Rect::new(0.0, 0.0, GLYPH_WIDTH, 1000.0));
+        font.matrix([0.001, 0.0, 0.0, 0.001, 0.0, 0.0]);
+        font.first_char(1);
+        font.last_char(chars.len() as u8);
+        font.widths(std::iter::repeat_n(GLYPH_WIDTH, chars.len()));
+        font.to_unicode(cmap_id);
+        font.encoding_custom()
+            .differences()
+            .consecutive(1, names.iter().map(|name| Name(name.as_bytes())));
+        font.char_procs()
+            .pairs(names.iter().map(|name| (Name(name.as_bytes()), glyph_id)));
+        font.finish();
+
+        let mut cmap = UnicodeCmap::<u8>::new(Name(b"Custom"), SystemInfo {
+            registry: Str(b"Adobe"),
+            ordering: Str(b"Identity"),
+            supplement: 0,
+        });
+        for (code, &c) in chars.iter().enumerate() {
+            cmap.pair(code as u8 + 1, c);
+        }
+        pdf.cmap(cmap_id, &cmap.finish());
+    }
+
+    let font_names: Vec<String> = (0..font_ids.len()).map(|index| format!("F{}", index)).collect();
+
+    let mut content = Content::new();
+    content.save_state();
+    let (x, y) = to_pdf(0.0, height_px as f32);
+    content.transform([image_width * POINTS_PER_MM, 0.0, 0.0, image_height * POINTS_PER_MM, x, y]);
+    content.x_object(Name(b"Im0"));
+    content.restore_state();
+
+    content.begin_text();
+    content.set_text_rendering_mode(TextRenderingMode::Invisible);
+    for run in &page.text {
+        let count = run.text.chars().count();
+        if count == 0 || run.width <= 0.0 {
+            continue;
+        }
+
+        let size = run.size * scale;
+        let natural_width = count as f32 * GLYPH_WIDTH / 1000.0 * size;
+        let (x, y) = to_pdf(run.x, run.baseline);
+        // Clockwise on the image is clockwise on paper, where y points up
+        let (sin, cos) = run.rotation.to_radians().sin_cos();
+        content.set_text_matrix([cos, -sin, sin, cos, x, y]);
+        content.set_horizontal_scaling(run.width * scale / natural_width * 100.0);
+
+        for (font, codes) in layer.encode(&run.text) {
+            content.set_font(Name(font_names[font].as_bytes()), size);
+            content.show(Str(&codes));
+        }
+    }
+    content.end_text();
+    pdf.stream(content_id, &content.finish());
+
+    let mut pdf_page = pdf.page(page_id);
+    pdf_page.media_box(Rect::new(0.0, 0.0, paper_width * POINTS_PER_MM, paper_height * POINTS_PER_MM));
+    pdf_page.parent(page_tree_id);
+    pdf_page.contents(content_id);
+    let mut resources = pdf_page.resources();
+    resources.x_objects().pair(Name(b"Im0"), image_id);
+    resources
+        .fonts()
+        .pairs(font_names.iter().zip(&font_ids).map(|(name, id)| (Name(name.as_bytes()), *id)));
+    resources.finish();
+    pdf_page.finish();
+
+    write_info(&mut pdf, info_id, provenance);
+
+    pdf.finish()
+}
+
+/// Assigns every distinct character of the text layer a font and a
+/// single-byte code in it.
+struct TextLayer {
+    chars: Vec<char>,
+    codes: HashMap<char, usize>,
+}
+
+impl TextLayer {
+    fn new(runs: &[TextRun]) -> Self {
+        let mut chars = Vec::new();
+        let mut codes = HashMap::new();
+        for c in runs.iter().flat_map(|run| run.text.chars()) {
+            codes.entry(c).or_insert_with(|| {
+                chars.push(c);
+                chars.len() - 1
+            });
+        }
+        Self { chars, codes }
+    }
+
+    fn fonts(&self) -> impl Iterator<Item = &[char]> {
+        self.chars.chunks(CODES_PER_FONT)
+    }
+
+    /// Splits `text` into runs of codes that share a font.
+    fn encode(&self, text: &str) -> Vec<(usize, Vec<u8>)> {
+        let mut segments: Vec<(usize, Vec<u8>)> = Vec::new();
+        for c in text.chars() {
+            let index = self.codes[&c];
+            let (font, code) = (index / CODES_PER_FONT, (index % CODES_PER_FONT + 1) as u8);
+            match segments.last_mut() {
+                Some((last, codes)) if *last == font => codes.push(code),
+                _ => segments.push((font, vec![code])),
+            }
+        }
+        segments
+    }
+}
+
+fn write_info(pdf: &mut Pdf, id: Ref, provenance: &Provenance) {
+    let time = provenance.rendered_at;
+    let date = Date::new(time.year() as u16)
+        .month(time.month() as u8)
+        .day(time.day() as u8)
+        .hour(time.hour() as u8)
+        .minute(time.minute() as u8)
+        .second(time.second() as u8)
+        .utc_offset_hour(0)
+        .utc_offset_minute(0);
+    let software = provenance.software();
+
+    let mut info = pdf.document_info(id);
+    info.subject(TextStr(provenance::NOTICE));
+    info.keywords(TextStr("parody, generated"));
+    info.creator(TextStr(&software));
+    info.producer(TextStr(&software));
+    info.creation_date(date);
+    info.pair(Name(b"Template"), TextStr(&provenance.template));
+    info.pair(Name(b"RenderId"), TextStr(&provenance.render_id));
+}
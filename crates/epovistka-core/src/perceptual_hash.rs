@@ -0,0 +1,139 @@
+use std::f32::consts::PI;
+
+use image::imageops::{self, FilterType};
+use image::GrayImage;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Side of the image the DCT of pHash is computed on.
+const PHASH_SIZE: usize = 32;
+/// Side of the block of lowest frequencies pHash keeps.
+const PHASH_FREQUENCIES: usize = 8;
+/// Smallest region, in pixels, worth hashing on its own.
+const MIN_REGION_SIZE: u32 = 8;
+/// Brightness range of a region's dHash thumbnail, in 8-bit levels, below
+/// which the region counts as blank paper.
+const BLANK_CONTRAST: u8 = 32;
+
+/// Difference and DCT hashes of an image. Both stay within a few bits of each
+/// other across recompression, resizing and small edits, so near-duplicates
+/// are found by Hamming distance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PerceptualHash {
+    #[serde(serialize_with = "to_hex", deserialize_with = "from_hex")]
+    pub dhash: u64,
+    #[serde(serialize_with = "to_hex", deserialize_with = "from_hex")]
+    pub phash: u64,
+}
+
+impl PerceptualHash {
+    pub fn of(image: &GrayImage) -> Self {
+        Self { dhash: dhash(image), phash: phash(image) }
+    }
+
+    /// Differing bits of the two hashes: `(dhash, phash)`.
+    pub fn distance(&self, other: &PerceptualHash) -> (u32, u32) {
+        ((self.dhash ^ other.dhash).count_ones(), (self.phash ^ other.phash).count_ones())
+    }
+}
+
+fn dhash_thumbnail(image: &GrayImage) -> GrayImage {
+    imageops::resize(image, 9, 8, FilterType::Triangle)
+}
+
+/// One bit per pair of horizontally adjacent pixels of a 9x8 thumbnail: set
+/// when brightness increases to the right.
+fn dhash(image: &GrayImage) -> u64 {
+    let thumbnail = dhash_thumbnail(image);
+    let mut hash = 0;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+            if thumbnail.get_pixel(x, y).0[0] < thumbnail.get_pixel(x + 1, y).0[0] {
+                hash |= 1;
+            }
+        }
+    }
+    hash
+}
+
+/// One bit per low-frequency DCT coefficient of a 32x32 thumbnail: set when
+/// the coefficient is above their median. The DC term is skipped.
+fn phash(image: &GrayImage) -> u64 {
+    let thumbnail = imageops::resize(image, PHASH_SIZE as u32, PHASH_SIZE as u32, FilterType::Triangle);
+    let pixels: Vec<f32> = thumbnail.pixels().map(|pixel| pixel.0[0] as f32).collect();
+
+    // cos((2x + 1) u π / 2N) for the frequencies kept
+    let basis: Vec<f32> = (0..PHASH_FREQUENCIES)
+        .flat_map(|u| {
+            (0..PHASH_SIZE).map(move |x| ((2 * x + 1) as f32 * u as f32 * PI / (2 * PHASH_SIZE) as f32).cos())
+        })
+        .collect();
+
+    let mut coefficients = Vec::with_capacity(PHASH_FREQUENCIES * PHASH_FREQUENCIES);
+    for v in 0..PHASH_FREQUENCIES {
+        for u in 0..PHASH_FREQUENCIES {
+            let mut sum = 0.0;
+            for y in 0..PHASH_SIZE {
+                for x in 0..PHASH_SIZE {
+                    sum += pixels[y * PHASH_SIZE + x] * basis[u * PHASH_SIZE + x] * basis[v * PHASH_SIZE + y];
+                }
+            }
+            coefficients.push(sum);
+        }
+    }
+
+    let mut sorted = coefficients[1..].to_vec();
+    sorted.sort_by(f32::total_cmp);
+    let median = sorted[sorted.len() / 2];
+
+    coefficients.iter().skip(1).fold(0, |hash, &coefficient| (hash << 1) | u64::from(coefficient > median))
+}
+
+/// Hashes of one part of an image. Renders of a template share everything
+/// but their text, so whole-page hashes barely tell them apart; hashing the
+/// text's regions on their own does.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RegionHash {
+    /// Left, top, right and bottom edge as fractions of the image size, so
+    /// the same region can be found in a resized copy.
+    pub bounds: [f32; 4],
+    pub hash: PerceptualHash,
+}
+
+impl RegionHash {
+    /// Hashes the part of `image` within `bounds`. `None` when that part is
+    /// too small to hash. Blank parts all get a hash of zeros: what decides
+    /// the bits of a hash of bare paper is noise, which recompression changes.
+    pub fn of(image: &GrayImage, bounds: [f32; 4]) -> Option<Self> {
+        let (width, height) = image.dimensions();
+        let [left, top, right, bottom] = bounds;
+        let x = (left * width as f32).round().max(0.0) as u32;
+        let y = (top * height as f32).round().max(0.0) as u32;
+        let right = ((right * width as f32).round() as u32).min(width);
+        let bottom = ((bottom * height as f32).round() as u32).min(height);
+        if right < x + MIN_REGION_SIZE || bottom < y + MIN_REGION_SIZE {
+            return None;
+        }
+
+        let region = imageops::crop_imm(image, x, y, right - x, bottom - y).to_image();
+        let (darkest, lightest) = dhash_thumbnail(&region)
+            .pixels()
+            .fold((u8::MAX, u8::MIN), |(darkest, lightest), pixel| (darkest.min(pixel.0[0]), lightest.max(pixel.0[0])));
+        let hash = if lightest - darkest < BLANK_CONTRAST {
+            PerceptualHash { dhash: 0, phash: 0 }
+        } else {
+            PerceptualHash::of(&region)
+        };
+
+        Some(Self { bounds, hash })
+    }
+}
+
+fn to_hex<S: Serializer>(value: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&format!("{:016x}", value))
+}
+
+fn from_hex<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    u64::from_str_radix(&text, 16).map_err(serde::de::Error::custom)
+}
@@ -0,0 +1,117 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use rand::Rng;
+use sha2::{Digest, Sha256};
+
+/// Named after the application rather than this crate, so documents carry the
+/// same metadata whichever program embeds the renderer.
+pub const GENERATOR: &str = "epovistka";
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+pub const NOTICE: &str = "Parody generated by epovistka. This is not a real document.";
+
+/// Namespace of the generator-specific XMP properties.
+const XMP_NAMESPACE: &str = "urn:epovistka:provenance:1.0#";
+
+/// Where a rendered file came from, embedded in every output so that anyone
+/// receiving it can tell it is a generated parody.
+#[derive(Debug, Clone)]
+pub struct Provenance {
+    pub template: String,
+    pub rendered_at: DateTime<Utc>,
+    /// Identifier of this render in UUID format: random (v4), or derived
+    /// from the request (v8) when the render is seeded.
+    pub render_id: String,
+}
+
+impl Provenance {
+    pub fn new(template: &str, rendered_at: DateTime<Utc>) -> Self {
+        Self {
+            template: template.to_string(),
+            rendered_at,
+            render_id: uuid(rand::rng().random(), 4),
+        }
+    }
+
+    /// Provenance of a seeded render. The render id is a hash of `request`,
+    /// which must cover everything else the output depends on, so rendering
+    /// the same request at the same `rendered_at` reproduces the file exactly.
+    pub fn seeded(template: &str, rendered_at: DateTime<Utc>, request: &str) -> Self {
+        let digest = Sha256::new()
+            .chain_update(template)
+            .chain_update([0])
+            .chain_update(rendered_at.to_rfc3339())
+            .chain_update([0])
+            .chain_update(request)
+            .finalize();
+        let mut bytes = [0; 16];
+        bytes.copy_from_slice(&digest[..16]);
+
+        Self {
+            template: template.to_string(),
+            rendered_at,
+            render_id: uuid(bytes, 8),
+        }
+    }
+
+    /// Generator name and version, e.g. "epovistka 0.1.0".
+    pub fn software(&self) -> String {
+        format!("{} {}", GENERATOR, VERSION)
+    }
+
+    /// Render time in RFC 3339, UTC.
+    pub fn timestamp(&self) -> String {
+        self.rendered_at.to_rfc3339_opts(SecondsFormat::Secs, true)
+    }
+
+    /// XMP packet describing the render, for formats that carry XMP.
+    pub fn xmp(&self) -> String {
+        format!(
+            concat!(
+                "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n",
+                "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n",
+                " <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n",
+                "  <rdf:Description rdf:about=\"\"\n",
+                "    xmlns:xmp=\"http://ns.adobe.com/xap/1.0/\"\n",
+                "    xmlns:dc=\"http://purl.org/dc/elements/1.1/\"\n",
+                "    xmlns:epovistka=\"{namespace}\"\n",
+                "    xmp:CreatorTool=\"{software}\"\n",
+                "    xmp:CreateDate=\"{timestamp}\"\n",
+                "    epovistka:Generator=\"{generator}\"\n",
+                "    epovistka:Version=\"{version}\"\n",
+                "    epovistka:Template=\"{template}\"\n",
+                "    epovistka:RenderId=\"{render_id}\"\n",
+                "    epovistka:Parody=\"True\">\n",
+                "   <dc:description>\n",
+                "    <rdf:Alt><rdf:li xml:lang=\"x-default\">{notice}</rdf:li></rdf:Alt>\n",
+                "   </dc:description>\n",
+                "  </rdf:Description>\n",
+                " </rdf:RDF>\n",
+                "</x:xmpmeta>\n",
+                "<?xpacket end=\"r\"?>",
+            ),
+            namespace = XMP_NAMESPACE,
+            software = escape_xml(&self.software()),
+            timestamp = self.timestamp(),
+            generator = GENERATOR,
+            version = VERSION,
+            template = escape_xml(&self.template),
+            render_id = self.render_id,
+            notice = escape_xml(NOTICE),
+        )
+    }
+}
+
+/// Formats `bytes` as a UUID of the given version, RFC 9562 variant.
+fn uuid(mut bytes: [u8; 16], version: u8) -> String {
+    bytes[6] = (bytes[6] & 0x0f) | (version << 4);
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}-{}-{}-{}-{}", &hex[0..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..32])
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,272 @@
+use image::{Rgba, RgbaImage};
+
+use crate::compositing::{self, BlendMode, LinearColor};
+use crate::template::Transform;
+
+/// Pixel rectangle, `min` inclusive and `max` exclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bounds {
+    pub min_x: i32,
+    pub min_y: i32,
+    pub max_x: i32,
+    pub max_y: i32,
+}
+
+impl Bounds {
+    fn include(&mut self, x: i32, y: i32) {
+        self.min_x = self.min_x.min(x);
+        self.min_y = self.min_y.min(y);
+        self.max_x = self.max_x.max(x + 1);
+        self.max_y = self.max_y.max(y + 1);
+    }
+}
+
+/// Transparent surface a single layer is drawn onto before it is composited
+/// onto the page. Tracks the area that was drawn on, so compositing and
+/// clearing only touch that part.
+#[derive(Debug)]
+pub struct Canvas {
+    image: RgbaImage,
+    bounds: Option<Bounds>,
+}
+
+impl Canvas {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self { image: RgbaImage::new(width, height), bounds: None }
+    }
+
+    /// A canvas holding `image`, with nothing marked as drawn.
+    pub fn from_image(image: RgbaImage) -> Self {
+        Self { image, bounds: None }
+    }
+
+    pub fn into_image(self) -> RgbaImage {
+        self.image
+    }
+
+    pub fn width(&self) -> u32 {
+        self.image.width()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.image.height()
+    }
+
+    /// Area drawn on since the canvas was created or cleared.
+    pub fn bounds(&self) -> Option<Bounds> {
+        self.bounds
+    }
+
+    /// Makes the drawn area transparent again.
+    pub fn clear(&mut self) {
+        if let Some(bounds) = self.bounds.take() {
+            for y in bounds.min_y..bounds.max_y {
+                for x in bounds.min_x..bounds.max_x {
+                    self.image.put_pixel(x as u32, y as u32, Rgba([0, 0, 0, 0]));
+                }
+            }
+        }
+    }
+
+    /// Paints `color` over the pixel at (`x`, `y`), made `opacity` times as
+    /// opaque. Pixels outside the canvas are ignored.
+    pub fn paint(&mut self, x: i32, y: i32, color: Rgba<u8>, opacity: f32) {
+        if x < 0 || y < 0 || x >= self.width() as i32 || y >= self.height() as i32 || opacity <= 0.0 || color[3] == 0 {
+            return;
+        }
+
+        let pixel = self.image.get_pixel_mut(x as u32, y as u32);
+        *pixel = compositing::composite(*pixel, color, opacity, BlendMode::Normal);
+        match &mut self.bounds {
+            Some(bounds) => bounds.include(x, y),
+            None => self.bounds = Some(Bounds { min_x: x, min_y: y, max_x: x + 1, max_y: y + 1 }),
+        }
+    }
+
+    /// Fills the axis-aligned rectangle from (`x0`, `y0`) to (`x1`, `y1`),
+    /// antialiasing edges that fall between pixels.
+    pub fn fill_rect(&mut self, x0: f32, y0: f32, x1: f32, y1: f32, color: Rgba<u8>) {
+        self.fill_frame(x0, y0, x1, y1, 0.0, color);
+    }
+
+    /// Fills the rectangle from (`x0`, `y0`) to (`x1`, `y1`) minus the one
+    /// `inset` pixels inside it. An `inset` of zero fills the whole rectangle.
+    pub fn fill_frame(&mut self, x0: f32, y0: f32, x1: f32, y1: f32, inset: f32, color: Rgba<u8>) {
+        let hole = (inset > 0.0).then_some((x0 + inset, y0 + inset, x1 - inset, y1 - inset));
+        for y in y0.floor() as i32..y1.ceil() as i32 {
+            for x in x0.floor() as i32..x1.ceil() as i32 {
+                let mut coverage = overlap(x, y, (x0, y0, x1, y1));
+                if let Some(hole) = hole {
+                    coverage -= overlap(x, y, hole);
+                }
+                self.paint(x, y, color, coverage);
+            }
+        }
+    }
+
+    /// Draws a straight line `width` pixels thick with round ends.
+    pub fn draw_line(&mut self, from: (f32, f32), to: (f32, f32), width: f32, color: Rgba<u8>) {
+        let radius = width / 2.0;
+        let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+        let length_squared = dx * dx + dy * dy;
+
+        let min_x = (from.0.min(to.0) - radius).floor() as i32;
+        let max_x = (from.0.max(to.0) + radius).ceil() as i32;
+        let min_y = (from.1.min(to.1) - radius).floor() as i32;
+        let max_y = (from.1.max(to.1) + radius).ceil() as i32;
+
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                // Distance from the pixel centre to the closest point of the segment
+                let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+                let t = if length_squared > 0.0 {
+                    (((px - from.0) * dx + (py - from.1) * dy) / length_squared).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+                let distance = (px - from.0 - t * dx).hypot(py - from.1 - t * dy);
+                self.paint(x, y, color, (radius + 0.5 - distance).clamp(0.0, 1.0));
+            }
+        }
+    }
+}
+
+/// Area of the pixel at (`x`, `y`) covered by a rectangle.
+fn overlap(x: i32, y: i32, (x0, y0, x1, y1): (f32, f32, f32, f32)) -> f32 {
+    let width = (x1.min(x as f32 + 1.0) - x0.max(x as f32)).max(0.0);
+    let height = (y1.min(y as f32 + 1.0) - y0.max(y as f32)).max(0.0);
+    width * height
+}
+
+/// Where a layer's canvas lands on the page: its [`Transform`] resolved
+/// against what the layer drew.
+#[derive(Debug, Clone, Copy)]
+pub struct Placement {
+    origin: (f32, f32),
+    translate: (f32, f32),
+    cos: f32,
+    sin: f32,
+    scale: f32,
+}
+
+impl Placement {
+    /// Resolves `transform` for a layer that drew inside `bounds`. Rotation and
+    /// scaling happen around the transform's origin or, if it has none, the
+    /// centre of the drawn area.
+    pub fn new(transform: &Transform, bounds: Option<Bounds>) -> Self {
+        let origin = match (transform.origin, bounds) {
+            (Some(origin), _) => (origin.x, origin.y),
+            (None, Some(bounds)) => (
+                (bounds.min_x + bounds.max_x) as f32 / 2.0,
+                (bounds.min_y + bounds.max_y) as f32 / 2.0,
+            ),
+            (None, None) => (0.0, 0.0),
+        };
+        let radians = transform.rotate.to_radians();
+
+        Self {
+            origin,
+            translate: (transform.translate.x, transform.translate.y),
+            cos: radians.cos(),
+            sin: radians.sin(),
+            scale: transform.scale,
+        }
+    }
+
+    pub fn is_identity(&self) -> bool {
+        self.translate == (0.0, 0.0) && self.sin == 0.0 && self.cos == 1.0 && self.scale == 1.0
+    }
+
+    /// Clockwise rotation in degrees.
+    pub fn rotation(&self) -> f32 {
+        self.sin.atan2(self.cos).to_degrees()
+    }
+
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    /// Page position of the canvas point (`x`, `y`).
+    pub fn apply(&self, x: f32, y: f32) -> (f32, f32) {
+        let (rx, ry) = ((x - self.origin.0) * self.scale, (y - self.origin.1) * self.scale);
+        (
+            self.origin.0 + self.translate.0 + rx * self.cos - ry * self.sin,
+            self.origin.1 + self.translate.1 + rx * self.sin + ry * self.cos,
+        )
+    }
+
+    /// Canvas position that lands on the page point (`x`, `y`).
+    fn invert(&self, x: f32, y: f32) -> (f32, f32) {
+        let (rx, ry) = (x - self.origin.0 - self.translate.0, y - self.origin.1 - self.translate.1);
+        (
+            self.origin.0 + (rx * self.cos + ry * self.sin) / self.scale,
+            self.origin.1 + (-rx * self.sin + ry * self.cos) / self.scale,
+        )
+    }
+
+    /// Composites the drawn part of `canvas` onto `page`, made `opacity` times
+    /// as opaque and mixed with `mode`.
+    pub fn composite(&self, page: &mut RgbaImage, canvas: &Canvas, opacity: f32, mode: BlendMode) {
+        let Some(bounds) = canvas.bounds() else {
+            return;
+        };
+
+        if self.is_identity() {
+            for y in bounds.min_y..bounds.max_y {
+                for x in bounds.min_x..bounds.max_x {
+                    let (x, y) = (x as u32, y as u32);
+                    if x < page.width() && y < page.height() {
+                        let pixel = page.get_pixel_mut(x, y);
+                        *pixel = compositing::composite(*pixel, *canvas.image.get_pixel(x, y), opacity, mode);
+                    }
+                }
+            }
+            return;
+        }
+
+        // Page area the transformed bounds cover
+        let corners = [
+            self.apply(bounds.min_x as f32, bounds.min_y as f32),
+            self.apply(bounds.max_x as f32, bounds.min_y as f32),
+            self.apply(bounds.min_x as f32, bounds.max_y as f32),
+            self.apply(bounds.max_x as f32, bounds.max_y as f32),
+        ];
+        let min_x = corners.iter().map(|c| c.0).fold(f32::INFINITY, f32::min).floor().max(0.0) as u32;
+        let min_y = corners.iter().map(|c| c.1).fold(f32::INFINITY, f32::min).floor().max(0.0) as u32;
+        let max_x = (corners.iter().map(|c| c.0).fold(f32::NEG_INFINITY, f32::max).ceil().max(0.0) as u32).min(page.width());
+        let max_y = (corners.iter().map(|c| c.1).fold(f32::NEG_INFINITY, f32::max).ceil().max(0.0) as u32).min(page.height());
+
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                let (sx, sy) = self.invert(x as f32 + 0.5, y as f32 + 0.5);
+                let source = sample(canvas, bounds, sx - 0.5, sy - 0.5);
+                if source.a > 0.0 {
+                    let pixel = page.get_pixel_mut(x, y);
+                    *pixel = compositing::composite(*pixel, source.to_srgba(), opacity, mode);
+                }
+            }
+        }
+    }
+}
+
+/// Bilinear sample of the canvas in linear light, transparent outside `bounds`.
+fn sample(canvas: &Canvas, bounds: Bounds, x: f32, y: f32) -> LinearColor {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let (x0, y0) = (x0 as i32, y0 as i32);
+
+    let mut color = LinearColor::default();
+    for (dx, dy, weight) in [(0, 0, (1.0 - fx) * (1.0 - fy)), (1, 0, fx * (1.0 - fy)), (0, 1, (1.0 - fx) * fy), (1, 1, fx * fy)] {
+        let (px, py) = (x0 + dx, y0 + dy);
+        if weight > 0.0 && px >= bounds.min_x && px < bounds.max_x && py >= bounds.min_y && py < bounds.max_y {
+            let texel = LinearColor::from_srgba(*canvas.image.get_pixel(px as u32, py as u32)).with_opacity(weight);
+            color = LinearColor {
+                r: color.r + texel.r,
+                g: color.g + texel.g,
+                b: color.b + texel.b,
+                a: color.a + texel.a,
+            };
+        }
+    }
+    color
+}
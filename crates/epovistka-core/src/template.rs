@@ -0,0 +1,798 @@
+use image::Rgba;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::path::{Component, Path, PathBuf};
+
+use crate::compositing::BlendMode;
+use crate::error::RenderError;
+use crate::locale::LocaleId;
+use crate::template_registry::FONTS_DIR;
+
+/// Layout description of a single document template, loaded from a JSON file
+/// that sits next to the template image.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TemplateManifest {
+    pub name: String,
+    pub image: String,
+    pub font: String,
+    #[serde(default)]
+    pub fallback_fonts: Vec<String>,
+    pub last_resort_font: Option<String>,
+    #[serde(default)]
+    pub shaping: ShapingMode,
+    #[serde(default)]
+    pub locale: LocaleId,
+    pub color: Color,
+    /// Everything drawn over the template image, bottom to top. Loading sorts
+    /// the layers by `z` and turns `fields` and `signature` into layers.
+    #[serde(default)]
+    pub layers: Vec<LayerSpec>,
+    /// Shorthand for text layers.
+    #[serde(default)]
+    fields: Vec<Shorthand<FieldSpec>>,
+    /// Shorthand for an image layer.
+    signature: Option<Shorthand<ImageSpec>>,
+    #[serde(default)]
+    pub watermark: WatermarkSpec,
+    #[serde(default)]
+    pub page: PageSpec,
+    #[serde(skip)]
+    base_dir: PathBuf,
+}
+
+/// Text layout engine used for the template's fonts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShapingMode {
+    /// OpenType shaping: kerning, ligatures and mark positioning.
+    #[default]
+    Harfbuzz,
+    /// Plain advance widths with legacy `kern` table kerning.
+    Basic,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct Color(pub [u8; 4]);
+
+impl From<Color> for Rgba<u8> {
+    fn from(color: Color) -> Self {
+        Rgba(color.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub struct Point {
+    pub x: f32,
+    pub y: f32,
+}
+
+/// Anchor of a field occurrence: the top-left corner of the text and,
+/// optionally, the box the text has to fit into.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct Anchor {
+    pub x: f32,
+    pub y: f32,
+    pub width: Option<f32>,
+    pub height: Option<f32>,
+}
+
+/// Inclusive `[min, max]` pair that a value is randomly drawn from.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct Span(pub f32, pub f32);
+
+impl Span {
+    pub fn sample(&self, rng: &mut impl Rng) -> f32 {
+        if self.0 < self.1 {
+            rng.random_range(self.0..=self.1)
+        } else {
+            self.0
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct Jitter {
+    pub x: Span,
+    pub y: Span,
+}
+
+impl Default for Jitter {
+    fn default() -> Self {
+        Self { x: Span(0.0, 0.0), y: Span(0.0, 0.0) }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RequestField {
+    Name,
+    Address,
+}
+
+/// Where the text of a field comes from.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ValueSource {
+    Request { field: RequestField },
+    Static { text: String },
+    /// The document number, optionally with thousands separators.
+    Number {
+        #[serde(default)]
+        grouped: bool,
+    },
+    /// Two-digit year.
+    Year,
+    /// Month name as written inside a date.
+    Month,
+    /// Day of the month.
+    Day,
+    /// The document date in a locale pattern (see [`crate::locale::Locale`]), or the
+    /// locale's default date format.
+    Date { pattern: Option<String> },
+    /// The appointment time in a locale pattern, or the locale's default.
+    Time { pattern: Option<String> },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FitStrategy {
+    /// Reduce the font size, down to `min_size`.
+    Shrink,
+    /// Tighten letter spacing, by at most `max_condense` of the font size per glyph.
+    Condense,
+    /// Cut the text and append an ellipsis.
+    Ellipsize,
+}
+
+/// How text that is too large for its box is made to fit. Strategies are
+/// tried in order until the text fits.
+#[derive(Debug, Clone, Deserialize)]
+pub struct FitSpec {
+    #[serde(default = "FitSpec::default_strategies")]
+    pub strategies: Vec<FitStrategy>,
+    pub min_size: Option<f32>,
+    #[serde(default = "FitSpec::default_max_condense")]
+    pub max_condense: f32,
+}
+
+impl FitSpec {
+    fn default_strategies() -> Vec<FitStrategy> {
+        vec![FitStrategy::Shrink, FitStrategy::Condense, FitStrategy::Ellipsize]
+    }
+
+    fn default_max_condense() -> f32 {
+        0.08
+    }
+}
+
+impl Default for FitSpec {
+    fn default() -> Self {
+        Self {
+            strategies: Self::default_strategies(),
+            min_size: None,
+            max_condense: Self::default_max_condense(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct FieldSpec {
+    pub name: String,
+    pub source: ValueSource,
+    /// Single-line occurrences of the field.
+    #[serde(default)]
+    pub positions: Vec<Anchor>,
+    /// Multi-line occurrences: each is an ordered list of line boxes the
+    /// value is word-wrapped across.
+    #[serde(default)]
+    pub flows: Vec<Vec<Anchor>>,
+    #[serde(default)]
+    pub jitter: Jitter,
+    pub size: Span,
+    pub color: Option<Color>,
+    #[serde(default)]
+    pub fit: FitSpec,
+}
+
+/// Image file, such as a signature or a stamp, placed with its top-left
+/// corner at each position.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ImageSpec {
+    pub image: String,
+    pub positions: Vec<Point>,
+    #[serde(default)]
+    pub jitter: Jitter,
+    #[serde(default = "ImageSpec::default_scale")]
+    pub scale: Span,
+}
+
+impl ImageSpec {
+    fn default_scale() -> Span {
+        Span(1.0, 1.0)
+    }
+}
+
+/// Rectangle with a fill, an outline drawn inside its edges, or both.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RectSpec {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub fill: Option<Color>,
+    pub stroke: Option<Color>,
+    #[serde(default = "default_stroke_width")]
+    pub stroke_width: f32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LineSpec {
+    pub from: Point,
+    pub to: Point,
+    /// Line colour; defaults to the template colour.
+    pub color: Option<Color>,
+    #[serde(default = "default_stroke_width")]
+    pub width: f32,
+}
+
+fn default_stroke_width() -> f32 {
+    1.0
+}
+
+/// QR code encoding a field value, with its top-left corner at (`x`, `y`).
+#[derive(Debug, Clone, Deserialize)]
+pub struct QrSpec {
+    pub source: ValueSource,
+    pub x: f32,
+    pub y: f32,
+    /// Side length in pixels, including the quiet zone. Modules are whole
+    /// pixels so the code stays sharp, which can make it slightly smaller.
+    pub size: f32,
+    /// Colour of dark modules; defaults to the template colour.
+    pub color: Option<Color>,
+    /// Fill behind the code and its quiet zone; transparent if not given.
+    pub background: Option<Color>,
+}
+
+/// What a layer draws.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LayerKind {
+    Text(FieldSpec),
+    Image(ImageSpec),
+    Rect(RectSpec),
+    Line(LineSpec),
+    Qr(QrSpec),
+    /// The parody watermark configured under `watermark`. Has to be the
+    /// topmost layer; if no layer places it, it is added on top.
+    Watermark,
+}
+
+/// Geometric transform of a whole layer.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct Transform {
+    /// Offset in pixels.
+    pub translate: Point,
+    /// Clockwise rotation in degrees.
+    pub rotate: f32,
+    pub scale: f32,
+    /// Point the layer is rotated and scaled around; defaults to the centre
+    /// of what the layer draws.
+    pub origin: Option<Point>,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self { translate: Point::default(), rotate: 0.0, scale: 1.0, origin: None }
+    }
+}
+
+/// One element of the document. Each layer is drawn on its own and then
+/// composited onto the layers below it.
+#[derive(Debug, Clone, Deserialize)]
+pub struct LayerSpec {
+    /// Stacking order: higher layers are drawn later. Layers with equal `z`
+    /// keep their order in the manifest.
+    #[serde(default)]
+    pub z: i32,
+    #[serde(default = "LayerSpec::default_opacity")]
+    pub opacity: f32,
+    #[serde(default)]
+    pub transform: Transform,
+    /// How the layer mixes with the layers below it.
+    #[serde(default)]
+    pub blend: BlendMode,
+    #[serde(flatten)]
+    pub kind: LayerKind,
+}
+
+impl LayerSpec {
+    fn default_opacity() -> f32 {
+        1.0
+    }
+
+    fn new(kind: LayerKind, blend: BlendMode) -> Self {
+        Self {
+            z: 0,
+            opacity: Self::default_opacity(),
+            transform: Transform::default(),
+            blend,
+            kind,
+        }
+    }
+}
+
+/// Entry of the `fields` and `signature` shorthands: a layer that only sets
+/// its blend mode.
+#[derive(Debug, Clone, Deserialize)]
+struct Shorthand<T> {
+    #[serde(flatten)]
+    spec: T,
+    #[serde(default)]
+    blend: BlendMode,
+}
+
+/// How watermark stamps are spread over the document.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WatermarkPlacement {
+    /// A few stamps at random positions, sizes and angles.
+    #[default]
+    Scattered,
+    /// A regular grid of identical stamps covering the whole page.
+    Tiled,
+}
+
+/// Parody mark stamped over the document: an image, or `text` drawn with the
+/// template's fonts when no image is given.
+#[derive(Debug, Clone, Deserialize)]
+pub struct WatermarkSpec {
+    /// Whether documents are watermarked when the request does not say.
+    #[serde(default = "WatermarkSpec::default_enabled")]
+    pub enabled: bool,
+    pub image: Option<String>,
+    #[serde(default = "WatermarkSpec::default_text")]
+    pub text: String,
+    /// Pixel size the text is drawn at before `scale` applies.
+    #[serde(default = "WatermarkSpec::default_size")]
+    pub size: f32,
+    /// Text colour; defaults to the template colour.
+    pub color: Option<Color>,
+    #[serde(default)]
+    pub placement: WatermarkPlacement,
+    /// Space between tiled stamps, in pixels.
+    #[serde(default = "WatermarkSpec::default_gap")]
+    pub gap: f32,
+    #[serde(default = "WatermarkSpec::default_opacity")]
+    pub opacity: Span,
+    #[serde(default = "WatermarkSpec::default_scale")]
+    pub scale: Span,
+    /// Rotation in degrees.
+    #[serde(default = "WatermarkSpec::default_rotation")]
+    pub rotation: Span,
+    pub disclaimer: Option<DisclaimerSpec>,
+    /// How stamps mix with the document below them, unless a watermark layer
+    /// in `layers` sets its own blend mode.
+    #[serde(default)]
+    pub blend: BlendMode,
+}
+
+impl WatermarkSpec {
+    fn default_enabled() -> bool {
+        true
+    }
+
+    fn default_text() -> String {
+        "ПАРОДІЯ / NOT A REAL DOCUMENT".to_string()
+    }
+
+    fn default_size() -> f32 {
+        48.0
+    }
+
+    fn default_gap() -> f32 {
+        120.0
+    }
+
+    fn default_opacity() -> Span {
+        Span(0.04, 0.1)
+    }
+
+    fn default_scale() -> Span {
+        Span(0.3, 0.9)
+    }
+
+    fn default_rotation() -> Span {
+        Span(-40.0, 40.0)
+    }
+}
+
+impl Default for WatermarkSpec {
+    fn default() -> Self {
+        Self {
+            enabled: Self::default_enabled(),
+            image: None,
+            text: Self::default_text(),
+            size: Self::default_size(),
+            color: None,
+            placement: WatermarkPlacement::default(),
+            gap: Self::default_gap(),
+            opacity: Self::default_opacity(),
+            scale: Self::default_scale(),
+            rotation: Self::default_rotation(),
+            disclaimer: None,
+            blend: BlendMode::default(),
+        }
+    }
+}
+
+/// Band along the bottom of the document that states it is not real. The
+/// band is added below the page so it never hides document content.
+#[derive(Debug, Clone, Deserialize)]
+pub struct DisclaimerSpec {
+    #[serde(default = "DisclaimerSpec::default_text")]
+    pub text: String,
+    #[serde(default = "DisclaimerSpec::default_height")]
+    pub height: f32,
+    #[serde(default = "DisclaimerSpec::default_size")]
+    pub size: f32,
+    #[serde(default = "DisclaimerSpec::default_color")]
+    pub color: Color,
+    #[serde(default = "DisclaimerSpec::default_background")]
+    pub background: Color,
+}
+
+impl DisclaimerSpec {
+    fn default_text() -> String {
+        "ПАРОДІЯ. НЕ Є ОФІЦІЙНИМ ДОКУМЕНТОМ / PARODY. NOT A REAL DOCUMENT".to_string()
+    }
+
+    fn default_height() -> f32 {
+        44.0
+    }
+
+    fn default_size() -> f32 {
+        24.0
+    }
+
+    fn default_color() -> Color {
+        Color([255, 255, 255, 255])
+    }
+
+    fn default_background() -> Color {
+        Color([170, 20, 20, 255])
+    }
+}
+
+/// Paper sizes documents can be printed on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PaperSize {
+    #[default]
+    A4,
+    A5,
+}
+
+impl PaperSize {
+    /// Portrait width and height in millimetres.
+    pub fn dimensions_mm(self) -> (f32, f32) {
+        match self {
+            PaperSize::A4 => (210.0, 297.0),
+            PaperSize::A5 => (148.0, 210.0),
+        }
+    }
+}
+
+/// Page margins in millimetres.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct Margins {
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+    pub left: f32,
+}
+
+impl Default for Margins {
+    fn default() -> Self {
+        Self { top: 10.0, right: 10.0, bottom: 10.0, left: 10.0 }
+    }
+}
+
+/// Physical size of the template: the resolution its image was drawn for and
+/// the page it is printed on. The image keeps its declared size on paper and
+/// is only scaled down when it does not fit between the margins.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct PageSpec {
+    #[serde(default)]
+    pub size: PaperSize,
+    #[serde(default = "PageSpec::default_dpi")]
+    pub dpi: f32,
+    #[serde(default)]
+    pub margins: Margins,
+}
+
+impl PageSpec {
+    fn default_dpi() -> f32 {
+        150.0
+    }
+}
+
+impl Default for PageSpec {
+    fn default() -> Self {
+        Self {
+            size: PaperSize::default(),
+            dpi: Self::default_dpi(),
+            margins: Margins::default(),
+        }
+    }
+}
+
+impl TemplateManifest {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, RenderError> {
+        let path = path.as_ref();
+        let data = std::fs::read_to_string(path).map_err(|source| RenderError::Io { path: path.to_path_buf(), source })?;
+        let manifest: Self = serde_json::from_str(&data).map_err(|e| {
+            RenderError::Template(format!("Failed to parse template manifest {}: {}", path.display(), e))
+        })?;
+
+        manifest.prepare(path.parent().map(Path::to_path_buf).unwrap_or_default())
+    }
+
+    /// Reads a manifest that is not stored yet, such as an editor draft, as if
+    /// it were loaded from `dir`.
+    pub fn from_value(value: serde_json::Value, dir: impl Into<PathBuf>) -> Result<Self, RenderError> {
+        let manifest: Self = serde_json::from_value(value)
+            .map_err(|e| RenderError::Template(format!("Failed to parse template manifest: {}", e)))?;
+
+        manifest.prepare(dir.into())
+    }
+
+    fn prepare(mut self, base_dir: PathBuf) -> Result<Self, RenderError> {
+        self.base_dir = base_dir;
+        self.build_layers();
+        self.validate().map_err(RenderError::Template)?;
+
+        Ok(self)
+    }
+
+    /// Turns the shorthands into layers below the declared ones, puts the
+    /// watermark on top unless a layer places it, and sorts by `z`.
+    fn build_layers(&mut self) {
+        let mut layers: Vec<LayerSpec> = self
+            .fields
+            .drain(..)
+            .map(|field| LayerSpec::new(LayerKind::Text(field.spec), field.blend))
+            .collect();
+        layers.extend(
+            self.signature
+                .take()
+                .map(|signature| LayerSpec::new(LayerKind::Image(signature.spec), signature.blend)),
+        );
+        layers.append(&mut self.layers);
+
+        if !layers.iter().any(|layer| matches!(layer.kind, LayerKind::Watermark)) {
+            layers.push(LayerSpec { z: i32::MAX, ..LayerSpec::new(LayerKind::Watermark, self.watermark.blend) });
+        }
+
+        // Stable, so layers with equal z keep their manifest order
+        layers.sort_by_key(|layer| layer.z);
+        self.layers = layers;
+    }
+
+    /// Template id: the name of the directory the manifest was loaded from.
+    pub fn id(&self) -> &str {
+        self.base_dir.file_name().and_then(|name| name.to_str()).unwrap_or_default()
+    }
+
+    /// Resolves an asset path from the manifest relative to the manifest's directory.
+    pub fn resolve(&self, asset: &str) -> PathBuf {
+        self.base_dir.join(asset)
+    }
+
+    /// Sources of every value the template draws: text fields and QR codes.
+    fn sources(&self) -> impl Iterator<Item = &ValueSource> {
+        self.layers.iter().filter_map(|layer| match &layer.kind {
+            LayerKind::Text(field) => Some(&field.source),
+            LayerKind::Qr(qr) => Some(&qr.source),
+            _ => None,
+        })
+    }
+
+    /// Text fields that draw a value which can differ between documents,
+    /// rather than fixed text, with their layers.
+    pub fn variable_fields(&self) -> impl Iterator<Item = (&LayerSpec, &FieldSpec)> {
+        self.layers.iter().filter_map(|layer| match &layer.kind {
+            LayerKind::Text(field) if !matches!(field.source, ValueSource::Static { .. }) => Some((layer, field)),
+            _ => None,
+        })
+    }
+
+    /// Fixed texts the template draws regardless of the request.
+    pub fn static_texts(&self) -> Vec<&str> {
+        let mut texts: Vec<&str> = self
+            .layers
+            .iter()
+            .filter_map(|layer| match &layer.kind {
+                LayerKind::Text(FieldSpec { source: ValueSource::Static { text }, .. }) => Some(text.as_str()),
+                _ => None,
+            })
+            .collect();
+
+        if self.watermark.image.is_none() {
+            texts.push(&self.watermark.text);
+        }
+        texts.extend(self.watermark.disclaimer.iter().map(|disclaimer| disclaimer.text.as_str()));
+        texts
+    }
+
+    /// Request fields that at least one text field or QR code draws its value from.
+    pub fn required_fields(&self) -> Vec<RequestField> {
+        let mut required = Vec::new();
+        for source in self.sources() {
+            if let ValueSource::Request { field } = source {
+                if !required.contains(field) {
+                    required.push(*field);
+                }
+            }
+        }
+        required
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let fonts = std::iter::once(&self.font).chain(&self.fallback_fonts).chain(&self.last_resort_font);
+        for font in fonts {
+            Self::validate_asset(font, true)?;
+        }
+        let images = self.layers.iter().filter_map(|layer| match &layer.kind {
+            LayerKind::Image(image) => Some(&image.image),
+            _ => None,
+        });
+        for image in std::iter::once(&self.image).chain(images).chain(&self.watermark.image) {
+            Self::validate_asset(image, false)?;
+        }
+
+        for (index, layer) in self.layers.iter().enumerate() {
+            if !(0.0..=1.0).contains(&layer.opacity) {
+                return Err(format!("Layer {} has an opacity outside [0, 1]", index));
+            }
+            if layer.transform.scale <= 0.0 {
+                return Err(format!("Layer {} has an invalid transform scale", index));
+            }
+
+            match &layer.kind {
+                LayerKind::Text(field) => Self::validate_field(field)?,
+                LayerKind::Image(image) => {
+                    if image.scale.0 <= 0.0 || image.scale.1 < image.scale.0 {
+                        return Err(format!("Image layer '{}' has an invalid scale range", image.image));
+                    }
+                }
+                LayerKind::Rect(rect) => {
+                    if rect.width <= 0.0 || rect.height <= 0.0 || rect.stroke_width <= 0.0 {
+                        return Err(format!("Rectangle layer {} has an empty size or stroke", index));
+                    }
+                    if rect.fill.is_none() && rect.stroke.is_none() {
+                        return Err(format!("Rectangle layer {} needs a fill or a stroke", index));
+                    }
+                }
+                LayerKind::Line(line) => {
+                    if line.width <= 0.0 {
+                        return Err(format!("Line layer {} has an invalid width", index));
+                    }
+                }
+                LayerKind::Qr(qr) => {
+                    if qr.size <= 0.0 {
+                        return Err(format!("QR layer {} has an invalid size", index));
+                    }
+                }
+                LayerKind::Watermark => {
+                    // Nothing may cover the parody mark
+                    if index + 1 != self.layers.len() {
+                        return Err("The watermark has to be the topmost layer".to_string());
+                    }
+                }
+            }
+        }
+
+        let watermark = &self.watermark;
+        if watermark.opacity.0 < 0.0 || watermark.opacity.1 > 1.0 || watermark.opacity.1 < watermark.opacity.0 {
+            return Err("Watermark opacity has to be a range within [0, 1]".to_string());
+        }
+        if watermark.scale.0 <= 0.0 || watermark.scale.1 < watermark.scale.0 || watermark.size <= 0.0 || watermark.gap < 0.0 {
+            return Err("Watermark has an invalid size, scale or gap".to_string());
+        }
+        if watermark.image.is_none() && watermark.text.trim().is_empty() {
+            return Err("Watermark needs an image or a text".to_string());
+        }
+        if let Some(disclaimer) = &watermark.disclaimer {
+            if disclaimer.height < 1.0 || disclaimer.size <= 0.0 {
+                return Err("Disclaimer band has an invalid height or text size".to_string());
+            }
+        }
+
+        let margins = self.page.margins;
+        if self.page.dpi <= 0.0 || [margins.top, margins.right, margins.bottom, margins.left].iter().any(|m| *m < 0.0) {
+            return Err("Page has an invalid resolution or margins".to_string());
+        }
+        for size in [PaperSize::A4, PaperSize::A5] {
+            let (width, height) = size.dimensions_mm();
+            if margins.left + margins.right >= width || margins.top + margins.bottom >= height {
+                return Err(format!("Page margins leave no room on {:?} paper", size));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Accepts paths within the template's directory and, for fonts, the
+    /// shared fonts next to it as `../fonts/<file>`, so that a manifest
+    /// cannot read files from anywhere else.
+    fn validate_asset(asset: &str, font: bool) -> Result<(), String> {
+        let components: Vec<Component> = Path::new(asset).components().collect();
+        let shared = matches!(components.as_slice(),
+            [Component::ParentDir, Component::Normal(dir), Component::Normal(_)] if *dir == FONTS_DIR);
+        let within = !components.is_empty()
+            && components.iter().all(|c| matches!(c, Component::CurDir | Component::Normal(_)));
+
+        if within || (font && shared) {
+            Ok(())
+        } else if font {
+            Err(format!("Font '{}' has to be in the template's directory or in ../{}", asset, FONTS_DIR))
+        } else {
+            Err(format!("Image '{}' has to be in the template's directory", asset))
+        }
+    }
+
+    fn validate_field(field: &FieldSpec) -> Result<(), String> {
+        if field.positions.is_empty() && field.flows.is_empty() {
+            return Err(format!("Field '{}' has no positions", field.name));
+        }
+        for flow in &field.flows {
+            if flow.is_empty() || flow.iter().any(|line| line.width.is_none()) {
+                return Err(format!("Every line of field '{}' needs a width", field.name));
+            }
+        }
+        if field.size.0 <= 0.0 || field.size.1 < field.size.0 {
+            return Err(format!("Field '{}' has an invalid size range", field.name));
+        }
+        if field.fit.min_size.is_some_and(|size| size <= 0.0) || field.fit.max_condense < 0.0 {
+            return Err(format!("Field '{}' has an invalid fit configuration", field.name));
+        }
+        for position in field.positions.iter().chain(field.flows.iter().flatten()) {
+            if position.width.is_some_and(|w| w <= 0.0) || position.height.is_some_and(|h| h <= 0.0) {
+                return Err(format!("Field '{}' has an empty box", field.name));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn assets_stay_within_the_template() {
+        for (asset, font) in [
+            ("template.png", false),
+            ("images/sign.png", false),
+            ("./font.ttf", true),
+            ("../fonts/DejaVuSans.ttf", true),
+        ] {
+            assert!(TemplateManifest::validate_asset(asset, font).is_ok(), "{}", asset);
+        }
+        for (asset, font) in [
+            ("", false),
+            ("/etc/passwd", false),
+            ("../other/template.png", false),
+            ("../fonts/DejaVuSans.ttf", false),
+            ("../fonts/../../secret.ttf", true),
+            ("../fonts/nested/font.ttf", true),
+            ("images/../../font.ttf", true),
+        ] {
+            assert!(TemplateManifest::validate_asset(asset, font).is_err(), "{}", asset);
+        }
+    }
+}
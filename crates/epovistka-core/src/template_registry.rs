@@ -0,0 +1,144 @@
+use image::{ExtendedColorType, ImageEncoder};
+use arc_swap::ArcSwap;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Arc;
+use tracing::{error, info};
+
+use crate::error::RenderError;
+use crate::image_generator::ImageGenerator;
+use crate::template::TemplateManifest;
+
+pub const DEFAULT_TEMPLATE: &str = "default";
+pub const MANIFEST_FILE: &str = "manifest.json";
+/// Directory of the templates directory holding fonts every template may use.
+/// Keeping them there means the template watcher sees them change.
+pub const FONTS_DIR: &str = "fonts";
+const PREVIEW_WIDTH: u32 = 240;
+
+/// Registry handle shared across threads; swapped atomically when templates are reloaded.
+pub type SharedRegistry = Arc<ArcSwap<TemplateRegistry>>;
+
+#[derive(Debug, Clone)]
+pub struct TemplateEntry {
+    pub generator: Arc<ImageGenerator>,
+    pub preview: Arc<Vec<u8>>,
+}
+
+/// All templates found under the templates directory, keyed by directory name.
+#[derive(Debug)]
+pub struct TemplateRegistry {
+    templates: BTreeMap<String, TemplateEntry>,
+    default_id: String,
+}
+
+impl TemplateRegistry {
+    pub fn load(dir: impl AsRef<Path>) -> Result<Self, RenderError> {
+        Self::scan(dir.as_ref(), None)
+    }
+
+    /// Rebuilds the registry from disk. Templates that fail to load keep the
+    /// version from `previous` so a broken asset never takes a template offline.
+    pub fn reload(dir: impl AsRef<Path>, previous: &Self) -> Result<Self, RenderError> {
+        Self::scan(dir.as_ref(), Some(previous))
+    }
+
+    fn scan(dir: &Path, previous: Option<&Self>) -> Result<Self, RenderError> {
+        let read_error = |source| RenderError::Io { path: dir.to_path_buf(), source };
+        let entries = std::fs::read_dir(dir).map_err(read_error)?;
+
+        let mut templates = BTreeMap::new();
+        for entry in entries {
+            let path = entry.map_err(read_error)?.path();
+            let manifest_path = path.join(MANIFEST_FILE);
+            if !manifest_path.is_file() {
+                continue;
+            }
+
+            let Some(id) = path.file_name().and_then(|name| name.to_str()).filter(|id| *id != FONTS_DIR) else {
+                continue;
+            };
+
+            match Self::load_template(&manifest_path) {
+                Ok(template) => {
+                    info!("Loaded template '{}'", id);
+                    templates.insert(id.to_string(), template);
+                }
+                Err(e) => match previous.and_then(|registry| registry.templates.get(id)) {
+                    Some(old) => {
+                        error!("Keeping previous version of template '{}': {}", id, e);
+                        templates.insert(id.to_string(), old.clone());
+                    }
+                    None => error!("Skipping template '{}': {}", id, e),
+                },
+            }
+        }
+
+        let default_id = if templates.contains_key(DEFAULT_TEMPLATE) {
+            DEFAULT_TEMPLATE.to_string()
+        } else {
+            templates
+                .keys()
+                .next()
+                .cloned()
+                .ok_or_else(|| RenderError::Template(format!("No templates found in {}", dir.display())))?
+        };
+
+        Ok(Self { templates, default_id })
+    }
+
+    pub fn load_template(manifest_path: &Path) -> Result<TemplateEntry, RenderError> {
+        let manifest = TemplateManifest::load(manifest_path)?;
+        let generator = ImageGenerator::from_manifest(manifest)?;
+        generator.check()?;
+        let preview = Self::render_preview(&generator)?;
+
+        Ok(TemplateEntry {
+            generator: Arc::new(generator),
+            preview: Arc::new(preview),
+        })
+    }
+
+    fn render_preview(generator: &ImageGenerator) -> Result<Vec<u8>, RenderError> {
+        let template = generator.template();
+        let height = (template.height() as u64 * PREVIEW_WIDTH as u64 / template.width().max(1) as u64).max(1) as u32;
+        let thumbnail = image::imageops::thumbnail(template, PREVIEW_WIDTH, height);
+
+        let mut bytes = Vec::new();
+        image::codecs::png::PngEncoder::new(&mut bytes)
+            .write_image(&thumbnail, thumbnail.width(), thumbnail.height(), ExtendedColorType::Rgba8)
+            .map_err(|e| RenderError::Failed(format!("Failed to encode the preview: {}", e)))?;
+
+        Ok(bytes)
+    }
+
+    /// Looks up a template by id, falling back to the default template when no id is given.
+    pub fn get(&self, id: Option<&str>) -> Result<Arc<ImageGenerator>, RenderError> {
+        let id = id.unwrap_or(&self.default_id);
+        self.templates
+            .get(id)
+            .map(|entry| entry.generator.clone())
+            .ok_or_else(|| RenderError::UnknownTemplate(id.to_string()))
+    }
+
+    /// Copy of the registry with template `id` added or replaced.
+    pub fn with_template(&self, id: &str, entry: TemplateEntry) -> Self {
+        let mut templates = self.templates.clone();
+        templates.insert(id.to_string(), entry);
+
+        let default_id = if id == DEFAULT_TEMPLATE { id.to_string() } else { self.default_id.clone() };
+        Self { templates, default_id }
+    }
+
+    pub fn entry(&self, id: &str) -> Option<&TemplateEntry> {
+        self.templates.get(id)
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = (&String, &TemplateEntry)> {
+        self.templates.iter()
+    }
+
+    pub fn default_id(&self) -> &str {
+        &self.default_id
+    }
+}
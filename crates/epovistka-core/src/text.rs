@@ -0,0 +1,551 @@
+use rusttype::{point, Font, GlyphId, PositionedGlyph, Scale, VMetrics};
+use std::borrow::Cow;
+
+use crate::fonts::LoadedFont;
+use crate::template::{FitSpec, FitStrategy, ShapingMode};
+
+const ELLIPSIS: &str = "…";
+const ASCII_ELLIPSIS: &str = "...";
+
+/// A line of text together with the size and letter spacing it should be drawn with.
+#[derive(Debug, Clone)]
+pub struct FittedText<'a> {
+    pub text: Cow<'a, str>,
+    pub scale: Scale,
+    pub spacing: f32,
+}
+
+/// A glyph placed relative to the start of the line's baseline.
+#[derive(Debug, Clone, Copy)]
+struct ShapedGlyph {
+    face: usize,
+    id: GlyphId,
+    x: f32,
+    y: f32,
+}
+
+/// One font of the chain, with its shaping face when OpenType shaping is on.
+struct ShapingFace<'a> {
+    font: &'a Font<'static>,
+    face: Option<rustybuzz::Face<'a>>,
+}
+
+/// Turns text into positioned glyphs. Text is split into runs by the first
+/// font in the chain that covers each character; with a HarfBuzz face the
+/// font's OpenType tables drive kerning, ligatures and mark placement, the
+/// basic path only applies advance widths and the legacy `kern` table.
+pub struct Shaper<'a> {
+    faces: Vec<ShapingFace<'a>>,
+}
+
+impl<'a> Shaper<'a> {
+    /// `fonts` must not be empty. Fonts whose shaping face cannot be parsed
+    /// fall back to basic layout.
+    pub fn new(fonts: &'a [LoadedFont], mode: ShapingMode) -> Self {
+        let faces = fonts
+            .iter()
+            .map(|loaded| ShapingFace {
+                font: &loaded.font,
+                face: match mode {
+                    ShapingMode::Harfbuzz => rustybuzz::Face::from_slice(&loaded.data, 0),
+                    ShapingMode::Basic => None,
+                },
+            })
+            .collect();
+
+        Self { faces }
+    }
+
+    /// Vertical metrics of the primary font.
+    pub fn v_metrics(&self, scale: Scale) -> VMetrics {
+        self.faces[0].font.v_metrics(scale)
+    }
+
+    /// Height in pixels of a line of text at `scale`.
+    pub fn line_height(&self, scale: Scale) -> f32 {
+        let v_metrics = self.v_metrics(scale);
+        v_metrics.ascent - v_metrics.descent
+    }
+
+    pub fn has_glyph(&self, c: char) -> bool {
+        self.faces.iter().any(|face| face.has_glyph(c))
+    }
+
+    /// Lays out a single line starting at `(x, y)`, where `y` is the baseline,
+    /// with `spacing` extra pixels between characters.
+    pub fn layout(&self, text: &str, scale: Scale, spacing: f32, x: f32, y: f32) -> Vec<PositionedGlyph<'a>> {
+        let (glyphs, _) = self.shape(text, scale, spacing);
+
+        glyphs
+            .into_iter()
+            .map(|glyph| {
+                self.faces[glyph.face]
+                    .font
+                    .glyph(glyph.id)
+                    .scaled(scale)
+                    .positioned(point(x + glyph.x, y + glyph.y))
+            })
+            .collect()
+    }
+
+    /// Width in pixels of `text` laid out on a single line.
+    pub fn measure(&self, text: &str, scale: Scale, spacing: f32) -> f32 {
+        self.shape(text, scale, spacing).1
+    }
+
+    fn shape(&self, text: &str, scale: Scale, spacing: f32) -> (Vec<ShapedGlyph>, f32) {
+        let mut glyphs = Vec::new();
+        let mut caret = 0.0;
+
+        for (i, (face, run)) in self.runs(text).into_iter().enumerate() {
+            if i > 0 {
+                caret += spacing;
+            }
+
+            let (run_glyphs, width) = self.faces[face].shape(run, scale, spacing);
+            glyphs.extend(run_glyphs.into_iter().map(|(id, x, y)| ShapedGlyph {
+                face,
+                id,
+                x: caret + x,
+                y,
+            }));
+            caret += width;
+        }
+
+        (glyphs, caret)
+    }
+
+    /// Splits text into runs that are each drawn with a single font.
+    /// Combining marks stay with their base character's font when it has them.
+    fn runs<'t>(&self, text: &'t str) -> Vec<(usize, &'t str)> {
+        let mut runs = Vec::new();
+        let mut current: Option<(usize, usize)> = None;
+
+        for (i, c) in text.char_indices() {
+            let face = match current {
+                Some((face, _)) if is_combining_mark(c) && self.faces[face].has_glyph(c) => face,
+                _ => self.face_for(c),
+            };
+
+            match current {
+                Some((current_face, _)) if current_face == face => {}
+                Some((current_face, start)) => {
+                    runs.push((current_face, &text[start..i]));
+                    current = Some((face, i));
+                }
+                None => current = Some((face, i)),
+            }
+        }
+
+        if let Some((face, start)) = current {
+            runs.push((face, &text[start..]));
+        }
+
+        runs
+    }
+
+    /// First font in the chain with a glyph for `c`; the last-resort font
+    /// draws its own missing-glyph box when nobody has one.
+    fn face_for(&self, c: char) -> usize {
+        self.faces
+            .iter()
+            .position(|face| face.has_glyph(c))
+            .unwrap_or(self.faces.len() - 1)
+    }
+}
+
+impl ShapingFace<'_> {
+    fn has_glyph(&self, c: char) -> bool {
+        self.font.glyph(c).id().0 != 0
+    }
+
+    /// Returns glyph ids with their offsets from the run origin, and the run width.
+    fn shape(&self, text: &str, scale: Scale, spacing: f32) -> (Vec<(GlyphId, f32, f32)>, f32) {
+        match &self.face {
+            Some(face) => self.shape_harfbuzz(face, text, scale, spacing),
+            None => self.shape_basic(text, scale, spacing),
+        }
+    }
+
+    fn shape_harfbuzz(
+        &self,
+        face: &rustybuzz::Face<'_>,
+        text: &str,
+        scale: Scale,
+        spacing: f32,
+    ) -> (Vec<(GlyphId, f32, f32)>, f32) {
+        let mut buffer = rustybuzz::UnicodeBuffer::new();
+        buffer.push_str(text);
+        let output = rustybuzz::shape(face, &[], buffer);
+
+        // rusttype scales fonts by their ascent-to-descent height, not by em size.
+        let v_metrics = self.font.v_metrics_unscaled();
+        let factor_y = scale.y / (v_metrics.ascent - v_metrics.descent);
+        let factor_x = factor_y * scale.x / scale.y;
+
+        let infos = output.glyph_infos();
+        let positions = output.glyph_positions();
+        let mut glyphs = Vec::with_capacity(infos.len());
+        let mut caret = 0.0;
+
+        for (i, (info, position)) in infos.iter().zip(positions).enumerate() {
+            glyphs.push((
+                GlyphId(info.glyph_id as u16),
+                caret + position.x_offset as f32 * factor_x,
+                -(position.y_offset as f32) * factor_y,
+            ));
+            caret += position.x_advance as f32 * factor_x;
+
+            // Letter spacing goes between clusters so combining marks stay on their base.
+            if infos.get(i + 1).is_some_and(|next| next.cluster != info.cluster) {
+                caret += spacing;
+            }
+        }
+
+        (glyphs, caret)
+    }
+
+    fn shape_basic(&self, text: &str, scale: Scale, spacing: f32) -> (Vec<(GlyphId, f32, f32)>, f32) {
+        let mut glyphs = Vec::with_capacity(text.len());
+        let mut caret = 0.0;
+        let mut previous = None;
+
+        for c in text.chars() {
+            let glyph = self.font.glyph(c);
+            if let Some(previous) = previous {
+                caret += self.font.pair_kerning(scale, previous, glyph.id()) + spacing;
+            }
+            previous = Some(glyph.id());
+
+            glyphs.push((glyph.id(), caret, 0.0));
+            caret += glyph.scaled(scale).h_metrics().advance_width;
+        }
+
+        (glyphs, caret)
+    }
+}
+
+fn is_combining_mark(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x0300..=0x036F | 0x0483..=0x0489 | 0x1AB0..=0x1AFF | 0x1DC0..=0x1DFF | 0x20D0..=0x20FF | 0xFE20..=0xFE2F
+    )
+}
+
+/// Applies the field's fitting strategies in order until the text fits into
+/// `width` x `height`. Whatever is left over after the last strategy is drawn
+/// as is.
+pub fn fit<'a>(
+    shaper: &Shaper,
+    text: &'a str,
+    size: f32,
+    spec: &FitSpec,
+    width: f32,
+    height: f32,
+) -> FittedText<'a> {
+    let mut fitted = FittedText {
+        text: Cow::Borrowed(text),
+        scale: Scale::uniform(size),
+        spacing: 0.0,
+    };
+
+    let min_size = spec.min_size.unwrap_or(size).min(size);
+
+    // Shrinking is the only strategy that helps with height, so apply it
+    // up front regardless of the configured order.
+    if spec.strategies.contains(&FitStrategy::Shrink) {
+        let current = shaper.line_height(fitted.scale);
+        if current > height {
+            fitted.scale = Scale::uniform((size * height / current).max(min_size));
+        }
+    }
+
+    for strategy in &spec.strategies {
+        let current = shaper.measure(&fitted.text, fitted.scale, fitted.spacing);
+        if current <= width {
+            break;
+        }
+
+        match strategy {
+            FitStrategy::Shrink => {
+                // Advance widths and kerning scale linearly with the font size.
+                let target = fitted.scale.x * width / current;
+                fitted.scale = Scale::uniform(target.max(min_size));
+            }
+            FitStrategy::Condense => {
+                let gaps = fitted.text.chars().count().saturating_sub(1);
+                if gaps > 0 {
+                    let max_condense = spec.max_condense * fitted.scale.x;
+                    let needed = (current - width) / gaps as f32;
+                    fitted.spacing -= needed.min(max_condense);
+                }
+            }
+            FitStrategy::Ellipsize => {
+                fitted.text = Cow::Owned(ellipsize(shaper, &fitted.text, fitted.scale, fitted.spacing, width));
+            }
+        }
+    }
+
+    fitted
+}
+
+/// Cuts `text` at a character boundary and appends an ellipsis so that the
+/// result is at most `width` pixels wide.
+fn ellipsize(shaper: &Shaper, text: &str, scale: Scale, spacing: f32, width: f32) -> String {
+    let ellipsis = if shaper.has_glyph('…') { ELLIPSIS } else { ASCII_ELLIPSIS };
+
+    let mut end = text.len();
+    while end > 0 {
+        end = text[..end].char_indices().last().map(|(i, _)| i).unwrap_or(0);
+        let candidate = format!("{}{}", text[..end].trim_end(), ellipsis);
+        if shaper.measure(&candidate, scale, spacing) <= width {
+            return candidate;
+        }
+    }
+
+    ellipsis.to_string()
+}
+
+/// Word-wraps `text` over consecutive lines of the given widths. Lines break
+/// after spaces, commas and hyphens, preferring to end a line on a comma;
+/// words longer than a whole line are hyphenated. Returns `None` when the
+/// text needs more lines than there are widths.
+pub fn wrap(
+    shaper: &Shaper,
+    text: &str,
+    scale: Scale,
+    spacing: f32,
+    widths: &[f32],
+) -> Option<Vec<String>> {
+    let fits = |line: &str, width: f32| shaper.measure(line.trim_end(), scale, spacing) <= width;
+
+    let mut lines: Vec<String> = Vec::new();
+    let mut pending: Vec<String> = segments(text).into_iter().map(str::to_string).collect();
+    pending.reverse();
+
+    let mut line: Vec<String> = Vec::new();
+    while let Some(segment) = pending.pop() {
+        let width = *widths.get(lines.len())?;
+        let candidate = format!("{}{}", line.concat(), segment);
+
+        if fits(&candidate, width) {
+            line.push(segment);
+            continue;
+        }
+
+        if line.is_empty() {
+            // The segment alone is wider than the line: hyphenate it.
+            let (head, tail) = hyphenate(shaper, &segment, scale, spacing, width)?;
+            lines.push(head);
+            pending.push(tail);
+            continue;
+        }
+
+        pending.push(segment);
+
+        // Prefer ending the line after a comma when that leaves only a short
+        // tail to carry over.
+        let comma = line.iter().rposition(|s| s.trim_end().ends_with(','));
+        if let Some(index) = comma.filter(|&i| i + 1 < line.len()) {
+            let tail_width = shaper.measure(&line[index + 1..].concat(), scale, spacing);
+            if tail_width <= width / 3.0 {
+                for carried in line.drain(index + 1..).rev() {
+                    pending.push(carried);
+                }
+            }
+        }
+
+        lines.push(line.concat().trim().to_string());
+        line.clear();
+    }
+
+    if !line.is_empty() {
+        widths.get(lines.len())?;
+        lines.push(line.concat().trim().to_string());
+    }
+
+    Some(lines)
+}
+
+/// Splits text into pieces that each end right after a break opportunity.
+fn segments(text: &str) -> Vec<&str> {
+    let mut segments = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        let next_is_space = chars.peek().is_some_and(|(_, next)| next.is_whitespace());
+        let breaks = c.is_whitespace() || ((c == ',' || c == '-') && !next_is_space);
+
+        if breaks {
+            let end = i + c.len_utf8();
+            segments.push(&text[start..end]);
+            start = end;
+        }
+    }
+
+    if start < text.len() {
+        segments.push(&text[start..]);
+    }
+
+    segments
+}
+
+/// Splits a word so that the head plus a hyphen fits into `width`. Keeps at
+/// least two characters on each side of the break.
+fn hyphenate(
+    shaper: &Shaper,
+    word: &str,
+    scale: Scale,
+    spacing: f32,
+    width: f32,
+) -> Option<(String, String)> {
+    let (word, trailing) = word.split_at(word.trim_end().len());
+    let boundaries: Vec<usize> = word.char_indices().map(|(i, _)| i).skip(2).collect();
+
+    boundaries
+        .iter()
+        .rev()
+        .filter(|&&i| word[i..].chars().count() >= 2)
+        .map(|&i| (format!("{}-", &word[..i]), &word[i..]))
+        .find(|(head, _)| shaper.measure(head, scale, spacing) <= width)
+        .map(|(head, tail)| (head, format!("{}{}", tail, trailing)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fonts::FontChain;
+    use crate::template::TemplateManifest;
+
+    const SIZE: f32 = 30.0;
+
+    fn with_shaper(test: impl FnOnce(&Shaper)) {
+        let manifest =
+            TemplateManifest::load(concat!(env!("CARGO_MANIFEST_DIR"), "/../../assets/templates/default/manifest.json"))
+                .unwrap();
+        let fonts = FontChain::load(&manifest).unwrap();
+        test(&fonts.shaper(ShapingMode::Harfbuzz));
+    }
+
+    fn spec(strategies: &[FitStrategy], min_size: Option<f32>) -> FitSpec {
+        FitSpec { strategies: strategies.to_vec(), min_size, max_condense: 0.08 }
+    }
+
+    fn ellipsis(shaper: &Shaper) -> &'static str {
+        if shaper.has_glyph('…') { ELLIPSIS } else { ASCII_ELLIPSIS }
+    }
+
+    #[test]
+    fn fit_leaves_text_that_fits_alone() {
+        with_shaper(|shaper| {
+            let all = spec(&[FitStrategy::Shrink, FitStrategy::Condense, FitStrategy::Ellipsize], None);
+            for text in ["Шевченко Тарас", ""] {
+                let fitted = fit(shaper, text, SIZE, &all, 1000.0, 100.0);
+                assert!(matches!(fitted.text, Cow::Borrowed(t) if t == text));
+                assert_eq!(fitted.scale, Scale::uniform(SIZE));
+                assert_eq!(fitted.spacing, 0.0);
+            }
+        });
+    }
+
+    #[test]
+    fn fit_shrinks_to_the_box_but_not_below_min_size() {
+        with_shaper(|shaper| {
+            let text = "Шевченко Тарас Григорович";
+            let width = shaper.measure(text, Scale::uniform(SIZE), 0.0);
+
+            let fitted = fit(shaper, text, SIZE, &spec(&[FitStrategy::Shrink], Some(10.0)), width * 0.8, 100.0);
+            assert!(fitted.scale.x < SIZE);
+            assert!(shaper.measure(text, fitted.scale, 0.0) <= width * 0.8 + 0.5);
+
+            let short = shaper.line_height(Scale::uniform(SIZE)) * 0.5;
+            let fitted = fit(shaper, text, SIZE, &spec(&[FitStrategy::Shrink], Some(10.0)), width * 2.0, short);
+            assert!(shaper.line_height(fitted.scale) <= short + 0.5);
+
+            let fitted = fit(shaper, text, SIZE, &spec(&[FitStrategy::Shrink], Some(24.0)), width * 0.2, 100.0);
+            assert_eq!(fitted.scale, Scale::uniform(24.0));
+
+            // A minimum above the template size never enlarges the text.
+            let fitted = fit(shaper, text, SIZE, &spec(&[FitStrategy::Shrink], Some(40.0)), width * 0.8, 100.0);
+            assert_eq!(fitted.scale, Scale::uniform(SIZE));
+        });
+    }
+
+    #[test]
+    fn fit_condenses_by_at_most_max_condense() {
+        with_shaper(|shaper| {
+            let text = "Шевченко Тарас Григорович";
+            let width = shaper.measure(text, Scale::uniform(SIZE), 0.0);
+
+            let fitted = fit(shaper, text, SIZE, &spec(&[FitStrategy::Condense], None), width - 5.0, 100.0);
+            assert!(fitted.spacing < 0.0);
+            assert!(shaper.measure(text, fitted.scale, fitted.spacing) <= width - 5.0 + 0.5);
+
+            let fitted = fit(shaper, text, SIZE, &spec(&[FitStrategy::Condense], None), width * 0.5, 100.0);
+            assert_eq!(fitted.spacing, -0.08 * SIZE);
+            assert_eq!(fitted.text, text);
+        });
+    }
+
+    #[test]
+    fn fit_ellipsizes_what_is_left() {
+        with_shaper(|shaper| {
+            let text = "Шевченко Тарас Григорович";
+            let width = shaper.measure(text, Scale::uniform(SIZE), 0.0) * 0.6;
+
+            let fitted = fit(shaper, text, SIZE, &spec(&[FitStrategy::Shrink, FitStrategy::Ellipsize], Some(SIZE)), width, 100.0);
+            assert!(fitted.text.ends_with(ellipsis(shaper)));
+            assert!(shaper.measure(&fitted.text, fitted.scale, fitted.spacing) <= width);
+        });
+    }
+
+    #[test]
+    fn ellipsize_cuts_at_characters_and_drops_trailing_spaces() {
+        with_shaper(|shaper| {
+            let scale = Scale::uniform(SIZE);
+            let ellipsis = ellipsis(shaper);
+
+            let expected = format!("Шевченко{}", ellipsis);
+            let width = shaper.measure(&expected, scale, 0.0);
+            assert_eq!(ellipsize(shaper, "Шевченко Тарас", scale, 0.0, width), expected);
+
+            assert_eq!(ellipsize(shaper, "Шевченко Тарас", scale, 0.0, 1.0), ellipsis);
+            assert_eq!(ellipsize(shaper, "", scale, 0.0, 1.0), ellipsis);
+        });
+    }
+
+    #[test]
+    fn wrap_edge_cases() {
+        with_shaper(|shaper| {
+            let scale = Scale::uniform(SIZE);
+            let wide = [10_000.0, 10_000.0];
+
+            assert_eq!(wrap(shaper, "", scale, 0.0, &wide), Some(vec![]));
+            assert_eq!(wrap(shaper, "м. Київ", scale, 0.0, &wide), Some(vec!["м. Київ".to_string()]));
+            assert_eq!(wrap(shaper, "м. Київ", scale, 0.0, &[]), None);
+
+            // The line ends on the last comma when only a short tail follows it.
+            let address = "Україна, м. Київ, вул. Хрещатик, 22";
+            let width = shaper.measure("Україна, м. Київ, вул.", scale, 0.0) + 1.0;
+            assert_eq!(
+                wrap(shaper, address, scale, 0.0, &[width, 10_000.0]),
+                Some(vec!["Україна, м. Київ,".to_string(), "вул. Хрещатик, 22".to_string()])
+            );
+            assert_eq!(wrap(shaper, address, scale, 0.0, &[width]), None);
+
+            // Words wider than a line are hyphenated, keeping two characters on each side.
+            let word = "Дніпропетровськ";
+            let width = shaper.measure(word, scale, 0.0) * 0.6;
+            let lines = wrap(shaper, word, scale, 0.0, &[width, width]).unwrap();
+            assert_eq!(lines.len(), 2);
+            let head = lines[0].strip_suffix('-').unwrap();
+            assert!(head.chars().count() >= 2 && lines[1].chars().count() >= 2);
+            assert_eq!(format!("{}{}", head, lines[1]), word);
+            assert!(lines.iter().all(|line| shaper.measure(line, scale, 0.0) <= width));
+
+            let narrow = shaper.measure("Дн-", scale, 0.0) - 1.0;
+            assert_eq!(wrap(shaper, word, scale, 0.0, &[narrow; 20]), None);
+        });
+    }
+}
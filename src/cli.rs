@@ -0,0 +1,350 @@
+use clap::{Args, Parser, Subcommand};
+use serde_json::{Map, Value};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use epovistka_core::{
+    image_generator::GeneratedImage,
+    output::OutputFormat,
+    template_registry::TemplateRegistry,
+};
+
+use crate::config::{Config, RenderSettings};
+use crate::models::batch::ManifestEntry;
+use crate::models::generate::{GenerateError, GenerateRequest};
+use crate::services::issued_registry::IssuedRegistry;
+
+/// Path argument that stands for standard input or output.
+const STDIO: &str = "-";
+
+/// Generator of parody summons: the HTTP server and an offline renderer.
+#[derive(Debug, Parser)]
+#[command(name = "epovistka", version)]
+pub struct Cli {
+    /// Directory holding the templates; overrides EPOVISTKA_TEMPLATES_DIR.
+    #[arg(long, global = true, value_name = "DIR")]
+    pub templates_dir: Option<PathBuf>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run the HTTP server. This is the default.
+    Serve,
+    /// Render documents without starting the server.
+    Render(Box<RenderArgs>),
+}
+
+/// Request fields are read from `--request` and overridden by flags. With
+/// `--batch`, flags fill in fields a line leaves out.
+#[derive(Debug, Args)]
+pub struct RenderArgs {
+    /// JSON file holding a /generate request body; "-" reads standard input.
+    #[arg(long, short, value_name = "FILE", conflicts_with = "batch")]
+    pub request: Option<PathBuf>,
+    /// NDJSON file with one request per line; "-" reads standard input.
+    /// Writes a document per line into the --output directory and a
+    /// result line per document to standard output.
+    #[arg(long, value_name = "FILE")]
+    pub batch: Option<PathBuf>,
+
+    #[arg(long)]
+    pub name: Option<String>,
+    #[arg(long)]
+    pub address: Option<String>,
+    /// Template id; the default template when omitted.
+    #[arg(long, short)]
+    pub template: Option<String>,
+    /// Seed for every random choice; the same request and seed give the same
+    /// document, byte for byte when EPOVISTKA_FIXED_TIME fixes the clock.
+    #[arg(long, short)]
+    pub seed: Option<u64>,
+    /// Document date, YYYY-MM-DD; defaults to today.
+    #[arg(long)]
+    pub date: Option<String>,
+    /// Locale for month names and numbers: uk, en, pl or de.
+    #[arg(long)]
+    pub locale: Option<String>,
+    /// png, jpeg, webp, webp-lossless, avif or pdf. Guessed from the
+    /// --output extension when omitted.
+    #[arg(long, short)]
+    pub format: Option<String>,
+    /// Quality (1-100) for lossy formats.
+    #[arg(long)]
+    pub quality: Option<u8>,
+    /// Paper size for PDF output: a4 or a5.
+    #[arg(long)]
+    pub paper: Option<String>,
+    /// Draw the parody watermark.
+    #[arg(long, overrides_with = "no_watermark")]
+    pub watermark: bool,
+    /// Leave the parody watermark out, unless the configuration makes it mandatory.
+    #[arg(long, overrides_with = "watermark")]
+    pub no_watermark: bool,
+
+    /// File to write the document to; standard output when omitted or "-".
+    /// With --batch, the directory to write the documents into.
+    #[arg(long, short, value_name = "PATH")]
+    pub output: Option<PathBuf>,
+}
+
+impl RenderArgs {
+    /// Request fields given as flags, in `/generate` request form.
+    fn fields(&self) -> Map<String, Value> {
+        let mut fields = Map::new();
+        let strings = [
+            ("name", &self.name),
+            ("address", &self.address),
+            ("template", &self.template),
+            ("date", &self.date),
+            ("locale", &self.locale),
+            ("format", &self.format),
+            ("paper", &self.paper),
+        ];
+        for (key, value) in strings {
+            if let Some(value) = value {
+                fields.insert(key.to_string(), Value::from(value.as_str()));
+            }
+        }
+        if let Some(seed) = self.seed {
+            fields.insert("seed".to_string(), Value::from(seed));
+        }
+        if let Some(quality) = self.quality {
+            fields.insert("quality".to_string(), Value::from(quality));
+        }
+        if self.watermark || self.no_watermark {
+            fields.insert("watermark".to_string(), Value::Bool(self.watermark));
+        }
+        fields
+    }
+
+    /// The `--output` file, unless it is standard output.
+    fn output_path(&self) -> Option<&Path> {
+        self.output.as_deref().filter(|path| *path != Path::new(STDIO))
+    }
+}
+
+/// Renders the document or batch `args` describe. Returns whether every
+/// document was rendered; a batch carries on past lines that fail.
+pub fn render(config: Config, args: RenderArgs) -> Result<bool, String> {
+    let renderer = Renderer::new(config)?;
+    match &args.batch {
+        Some(batch) => render_batch(&renderer, &args, batch),
+        None => render_one(&renderer, &args).map(|()| true),
+    }
+}
+
+fn render_one(renderer: &Renderer, args: &RenderArgs) -> Result<(), String> {
+    let mut fields = match &args.request {
+        Some(path) => request_fields(&read_input(path)?).map_err(|e| format!("Invalid request in {}: {}", path.display(), e))?,
+        None => Map::new(),
+    };
+    fields.extend(args.fields());
+
+    let output = args.output_path();
+    if !fields.contains_key("format") {
+        let guessed = output
+            .and_then(|path| path.extension()?.to_str())
+            .and_then(OutputFormat::from_extension);
+        if let Some(format) = guessed {
+            fields.insert("format".to_string(), serde_json::to_value(format).map_err(|e| e.to_string())?);
+        }
+    }
+
+    let request: GenerateRequest =
+        serde_json::from_value(Value::Object(fields)).map_err(|e| format!("Invalid request: {}", e))?;
+    let image = renderer.render(request).map_err(|e| e.to_string())?;
+
+    match output {
+        Some(path) => std::fs::write(path, &image.data).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?,
+        None => std::io::stdout()
+            .lock()
+            .write_all(&image.data)
+            .map_err(|e| format!("Failed to write the document: {}", e))?,
+    }
+
+    eprintln!("Rendered {} with seed {} (render {})", image.format.extension(), image.seed, image.render_id);
+    Ok(())
+}
+
+/// Renders every line of an NDJSON batch into `--output`, printing one
+/// [`ManifestEntry`] per line. Lines are numbered from 1; blank lines are skipped.
+fn render_batch(renderer: &Renderer, args: &RenderArgs, batch: &Path) -> Result<bool, String> {
+    let dir = args
+        .output_path()
+        .ok_or("--batch writes a file per line, pass the directory to write them into with --output")?;
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+
+    let input = String::from_utf8(read_input(batch)?).map_err(|e| format!("{} is not UTF-8: {}", batch.display(), e))?;
+    let lines: Vec<&str> = input.lines().collect();
+    let digits = lines.len().to_string().len().max(3);
+    let defaults = args.fields();
+
+    let mut stdout = std::io::stdout().lock();
+    let mut all_rendered = true;
+
+    for (index, line) in lines.iter().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let mut entry = ManifestEntry {
+            row: index + 1,
+            name: String::new(),
+            template: String::new(),
+            file: None,
+            seed: None,
+            format: None,
+            render_id: None,
+            error: None,
+        };
+
+        let rendered = request_fields(line.as_bytes()).and_then(|mut fields| {
+            for (key, value) in &defaults {
+                fields.entry(key.clone()).or_insert_with(|| value.clone());
+            }
+            let request: GenerateRequest = serde_json::from_value(Value::Object(fields)).map_err(|e| e.to_string())?;
+            entry.name = request.name.trim().to_string();
+            entry.template = renderer.template_id(&request);
+            renderer.render(request).map_err(|e| e.to_string())
+        });
+
+        match rendered {
+            Ok(image) => {
+                let file = format!("povistka-{:0width$}.{}", entry.row, image.format.extension(), width = digits);
+                let path = dir.join(&file);
+                std::fs::write(&path, &image.data).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+
+                entry.file = Some(file);
+                entry.seed = Some(image.seed);
+                entry.format = Some(image.format);
+                entry.render_id = Some(image.render_id);
+            }
+            Err(e) => {
+                entry.error = Some(e);
+                all_rendered = false;
+            }
+        }
+
+        serde_json::to_writer(&mut stdout, &entry).map_err(|e| e.to_string())?;
+        writeln!(stdout).map_err(|e| e.to_string())?;
+    }
+
+    Ok(all_rendered)
+}
+
+/// Parses a JSON request body into its fields.
+fn request_fields(json: &[u8]) -> Result<Map<String, Value>, String> {
+    match serde_json::from_slice(json).map_err(|e| e.to_string())? {
+        Value::Object(fields) => Ok(fields),
+        _ => Err("Expected a JSON object".to_string()),
+    }
+}
+
+fn read_input(path: &Path) -> Result<Vec<u8>, String> {
+    if path == Path::new(STDIO) {
+        let mut data = Vec::new();
+        std::io::stdin()
+            .read_to_end(&mut data)
+            .map_err(|e| format!("Failed to read standard input: {}", e))?;
+        Ok(data)
+    } else {
+        std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+    }
+}
+
+/// Renders requests the way `/generate` does, straight from the configuration.
+struct Renderer {
+    registry: TemplateRegistry,
+    settings: RenderSettings,
+    issued: Option<IssuedRegistry>,
+}
+
+impl Renderer {
+    fn new(config: Config) -> Result<Self, String> {
+        let registry =
+            TemplateRegistry::load(&config.templates_dir).map_err(|e| format!("Failed to load templates: {}", e))?;
+        let issued = match &config.registry_path {
+            Some(path) => {
+                Some(IssuedRegistry::open(path, config.registry_capacity).map_err(|e| format!("Failed to open the image registry: {}", e))?)
+            }
+            None => None,
+        };
+
+        Ok(Self { registry, settings: config.render_settings(), issued })
+    }
+
+    fn template_id(&self, request: &GenerateRequest) -> String {
+        request.template.clone().unwrap_or_else(|| self.registry.default_id().to_string())
+    }
+
+    fn render(&self, mut request: GenerateRequest) -> Result<GeneratedImage, GenerateError> {
+        request.sanitize();
+        request.validate()?;
+
+        let image_generator = self.registry.get(request.template.as_deref())?;
+        let options = self.settings.options(&request, OutputFormat::default());
+
+        let image = image_generator.generate_image(&request.render_request(), &options)?;
+        if let Some(issued) = &self.issued {
+            issued.record(image_generator.manifest().id(), options.rendered_at, &image);
+        }
+        Ok(image)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    use epovistka_core::fingerprint::Fingerprint;
+
+    use super::*;
+    use crate::middleware::admin::AdminToken;
+    use crate::services::clock::DEFAULT_TIMEZONE;
+    use crate::services::issued_registry::DEFAULT_CAPACITY;
+
+    fn config() -> Config {
+        Config {
+            templates_dir: PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/templates")),
+            hot_reload: false,
+            timezone: DEFAULT_TIMEZONE,
+            fixed_time: Some(
+                DateTime::parse_from_rfc3339("2025-03-14T09:30:00+02:00").unwrap().with_timezone(&DEFAULT_TIMEZONE),
+            ),
+            render_workers: 1,
+            render_queue: 0,
+            watermark_mandatory: false,
+            fingerprint: Some(Arc::new(Fingerprint::new("test secret"))),
+            registry_path: None,
+            registry_capacity: DEFAULT_CAPACITY,
+            admin_token: AdminToken::new(None),
+        }
+    }
+
+    #[test]
+    fn seeded_renders_with_a_fixed_clock_are_identical() {
+        let renderer = Renderer::new(config()).unwrap();
+
+        for format in ["png", "jpeg", "webp", "pdf"] {
+            let request = || {
+                serde_json::from_value::<GenerateRequest>(json!({
+                    "name": "Шевченко Тарас Григорович",
+                    "address": "м. Київ, вул. Хрещатик, 22, кв. 5",
+                    "seed": 42,
+                    "format": format,
+                }))
+                .unwrap()
+            };
+
+            let first = renderer.render(request()).unwrap();
+            let second = renderer.render(request()).unwrap();
+            assert_eq!(first.render_id, second.render_id, "{}", format);
+            assert!(first.data == second.data, "{} output differs between renders", format);
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use epovistka_core::{fingerprint::Fingerprint, image_generator::RenderOptions, output::OutputFormat};
+
+use crate::middleware::admin::AdminToken;
+use crate::models::generate::GenerateRequest;
+use crate::services::clock::{Clock, FixedClock, SystemClock, DEFAULT_TIMEZONE};
+use crate::services::issued_registry::DEFAULT_CAPACITY;
+
+/// Where templates are loaded from unless `EPOVISTKA_TEMPLATES_DIR` says otherwise.
+pub const TEMPLATES_DIR: &str = "assets/templates";
+
+/// Runtime settings read from `EPOVISTKA_*` environment variables.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub templates_dir: PathBuf,
+    pub hot_reload: bool,
+    /// IANA timezone that document dates are computed in.
+    pub timezone: Tz,
+    /// RFC 3339 instant to freeze the clock at, for reproducible deployments.
+    pub fixed_time: Option<DateTime<Tz>>,
+    /// Threads rendering documents; defaults to the number of CPUs.
+    pub render_workers: usize,
+    /// Renders allowed to wait for a free worker before requests are turned away.
+    pub render_queue: usize,
+    /// Watermark every document, ignoring the `watermark` request parameter.
+    pub watermark_mandatory: bool,
+    /// Invisible fingerprint keyed by `EPOVISTKA_FINGERPRINT_SECRET`; without
+    /// a secret documents are not fingerprinted and `/verify` is unavailable.
+    pub fingerprint: Option<Arc<Fingerprint>>,
+    /// JSON lines file recording a perceptual hash of every document issued;
+    /// nothing is recorded when unset.
+    pub registry_path: Option<PathBuf>,
+    /// Latest renders the registry keeps in memory for lookups.
+    pub registry_capacity: usize,
+    /// Token for the admin endpoints, from `EPOVISTKA_ADMIN_TOKEN`.
+    pub admin_token: AdminToken,
+}
+
+impl Config {
+    pub fn from_env() -> Result<Self, String> {
+        let timezone = match std::env::var("EPOVISTKA_TIMEZONE") {
+            Ok(name) => name
+                .parse::<Tz>()
+                .map_err(|e| format!("Invalid EPOVISTKA_TIMEZONE '{}': {}", name, e))?,
+            Err(_) => DEFAULT_TIMEZONE,
+        };
+
+        let fixed_time = match std::env::var("EPOVISTKA_FIXED_TIME") {
+            Ok(value) => Some(
+                DateTime::parse_from_rfc3339(&value)
+                    .map_err(|e| format!("Invalid EPOVISTKA_FIXED_TIME '{}': {}", value, e))?
+                    .with_timezone(&timezone),
+            ),
+            Err(_) => None,
+        };
+
+        let render_workers = env_number("EPOVISTKA_RENDER_WORKERS")?
+            .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get()));
+        let render_queue = env_number("EPOVISTKA_RENDER_QUEUE")?.unwrap_or(render_workers * 4);
+
+        Ok(Self {
+            templates_dir: std::env::var("EPOVISTKA_TEMPLATES_DIR")
+                .map(PathBuf::from)
+                .unwrap_or_else(|_| PathBuf::from(TEMPLATES_DIR)),
+            hot_reload: env_flag("EPOVISTKA_HOT_RELOAD", true),
+            timezone,
+            fixed_time,
+            render_workers,
+            render_queue,
+            watermark_mandatory: env_flag("EPOVISTKA_WATERMARK_MANDATORY", false),
+            fingerprint: std::env::var("EPOVISTKA_FINGERPRINT_SECRET")
+                .ok()
+                .filter(|secret| !secret.is_empty())
+                .map(|secret| Arc::new(Fingerprint::new(&secret))),
+            registry_path: std::env::var("EPOVISTKA_REGISTRY_PATH").ok().map(PathBuf::from),
+            registry_capacity: env_number("EPOVISTKA_REGISTRY_CAPACITY")?.unwrap_or(DEFAULT_CAPACITY),
+            admin_token: AdminToken::new(
+                std::env::var("EPOVISTKA_ADMIN_TOKEN").ok().filter(|token| !token.is_empty()),
+            ),
+        })
+    }
+
+    pub fn clock(&self) -> Arc<dyn Clock> {
+        match self.fixed_time {
+            Some(time) => Arc::new(FixedClock::new(time)),
+            None => Arc::new(SystemClock::new(self.timezone)),
+        }
+    }
+
+    pub fn render_settings(&self) -> RenderSettings {
+        RenderSettings {
+            clock: self.clock(),
+            watermark_mandatory: self.watermark_mandatory,
+            fingerprint: self.fingerprint.clone(),
+        }
+    }
+}
+
+/// The part of the configuration every render depends on, so that the
+/// endpoints and the command line turn requests into the same options.
+#[derive(Debug, Clone)]
+pub struct RenderSettings {
+    clock: Arc<dyn Clock>,
+    watermark_mandatory: bool,
+    fingerprint: Option<Arc<Fingerprint>>,
+}
+
+impl RenderSettings {
+    pub fn now(&self) -> DateTime<Tz> {
+        self.clock.now()
+    }
+
+    /// Options for rendering `request` now, in `fallback` unless the request
+    /// names a format.
+    pub fn options(&self, request: &GenerateRequest, fallback: OutputFormat) -> RenderOptions {
+        self.options_at(self.now(), request, fallback)
+    }
+
+    /// Options for rendering `request` at `now`; a batch renders every row
+    /// at the instant it was received.
+    pub fn options_at(&self, now: DateTime<Tz>, request: &GenerateRequest, fallback: OutputFormat) -> RenderOptions {
+        RenderOptions {
+            today: request.date.unwrap_or_else(|| now.date_naive()),
+            rendered_at: now.with_timezone(&Utc),
+            output: request.output_options(fallback),
+            watermark: if self.watermark_mandatory { Some(true) } else { request.watermark },
+            fingerprint: self.fingerprint.clone(),
+        }
+    }
+}
+
+fn env_flag(name: &str, default: bool) -> bool {
+    match std::env::var(name) {
+        Ok(value) => matches!(value.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes" | "on"),
+        Err(_) => default,
+    }
+}
+
+fn env_number(name: &str) -> Result<Option<usize>, String> {
+    match std::env::var(name) {
+        Ok(value) => value
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|e| format!("Invalid {} '{}': {}", name, value, e)),
+        Err(_) => Ok(None),
+    }
+}
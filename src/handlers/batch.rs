@@ -0,0 +1,208 @@
+use axum::{
+    body::Body,
+    response::{IntoResponse, Response},
+};
+use chrono::DateTime;
+use chrono_tz::Tz;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use tokio::{sync::mpsc, task::JoinHandle};
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::{info, warn};
+
+use epovistka_core::{
+    image_generator::{GeneratedImage, ImageGenerator},
+    output::OutputFormat,
+    template_registry::SharedRegistry,
+};
+
+use crate::{
+    config::RenderSettings,
+    models::batch::{parse_rows, BatchError, BatchFormat, ManifestEntry, RowError},
+    models::generate::{GenerateError, GenerateRequest},
+    services::{
+        archive::{self, ArchiveFile},
+        issued_registry::IssuedRegistry,
+        render_pool::RenderPool,
+    },
+};
+
+const MANIFEST_FILE: &str = "manifest.json";
+
+/// A validated row, ready to render.
+struct BatchRow {
+    row: usize,
+    template: String,
+    generator: Arc<ImageGenerator>,
+    request: GenerateRequest,
+}
+
+#[derive(Clone)]
+pub struct BatchHandler {
+    registry: SharedRegistry,
+    settings: RenderSettings,
+    pool: Arc<RenderPool>,
+    issued: Option<Arc<IssuedRegistry>>,
+}
+
+impl BatchHandler {
+    pub fn new(
+        registry: SharedRegistry,
+        settings: RenderSettings,
+        pool: Arc<RenderPool>,
+        issued: Option<Arc<IssuedRegistry>>,
+    ) -> Self {
+        Self { registry, settings, pool, issued }
+    }
+
+    /// Validates every row of a CSV or JSON batch and, if all are valid,
+    /// streams back a ZIP with one document per row plus `manifest.json`.
+    pub fn handle_batch_request(&self, content_type: Option<&str>, body: &[u8]) -> Result<Response, BatchError> {
+        let format = BatchFormat::detect(content_type, body);
+        let rows = self.validate(parse_rows(format, body)?)?;
+
+        info!("Processing batch of {} documents", rows.len());
+
+        let (file_tx, file_rx) = mpsc::channel(self.pool.workers());
+        let (body_tx, body_rx) = mpsc::channel(4);
+
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = archive::write_zip(file_rx, body_tx) {
+                warn!("Batch archive aborted: {}", e);
+            }
+        });
+        // Every row is rendered at the instant the batch arrived.
+        let now = self.settings.now();
+        tokio::spawn(render_rows(self.pool.clone(), rows, self.settings.clone(), now, self.issued.clone(), file_tx));
+
+        let headers = [
+            (http::header::CONTENT_TYPE, "application/zip"),
+            (
+                http::header::CONTENT_DISPOSITION,
+                "attachment; filename=\"povistky.zip\"",
+            ),
+        ];
+
+        Ok((headers, Body::from_stream(ReceiverStream::new(body_rx))).into_response())
+    }
+
+    /// Sanitizes and validates every row, collecting all errors instead of
+    /// stopping at the first one.
+    fn validate(&self, rows: Vec<Result<GenerateRequest, String>>) -> Result<Vec<BatchRow>, BatchError> {
+        let registry = self.registry.load();
+        let mut valid = Vec::with_capacity(rows.len());
+        let mut errors = Vec::new();
+
+        for (index, row) in rows.into_iter().enumerate() {
+            let row_number = index + 1;
+            let checked = row.map_err(GenerateError::ValidationError).and_then(|mut request| {
+                request.sanitize();
+                request.validate()?;
+                let generator = registry.get(request.template.as_deref())?;
+                let template = request.template.clone().unwrap_or_else(|| registry.default_id().to_string());
+                Ok(BatchRow { row: row_number, template, generator, request })
+            });
+
+            match checked {
+                Ok(row) => valid.push(row),
+                Err(e) => errors.push(RowError { row: row_number, error: e.to_string() }),
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(valid)
+        } else {
+            Err(BatchError::InvalidRows(errors))
+        }
+    }
+}
+
+/// Renders the rows with as many in flight as the pool runs queued jobs at
+/// once and forwards the documents to the archive in row order, followed by the manifest.
+async fn render_rows(
+    pool: Arc<RenderPool>,
+    rows: Vec<BatchRow>,
+    settings: RenderSettings,
+    now: DateTime<Tz>,
+    issued: Option<Arc<IssuedRegistry>>,
+    files: mpsc::Sender<ArchiveFile>,
+) {
+    let digits = rows.len().to_string().len().max(3);
+    let mut manifest = Vec::with_capacity(rows.len());
+    let mut pending = VecDeque::new();
+    let mut rows = rows.into_iter();
+
+    loop {
+        while pending.len() < pool.queued_workers() {
+            let Some(row) = rows.next() else { break };
+            pending.push_back(start_render(&pool, row, &settings, now, issued.clone()));
+        }
+        let Some((mut entry, job)) = pending.pop_front() else { break };
+
+        let result = job
+            .await
+            .map_err(|e| GenerateError::GenerationError(e.to_string()))
+            .and_then(|result| result);
+
+        match result {
+            Ok(image) => {
+                let name = format!("povistka-{:0width$}.{}", entry.row, image.format.extension(), width = digits);
+                entry.file = Some(name.clone());
+                entry.seed = Some(image.seed);
+                entry.format = Some(image.format);
+                entry.render_id = Some(image.render_id);
+
+                if files.send(ArchiveFile { name, data: image.data }).await.is_err() {
+                    // The archive writer stopped, most likely because the client went away.
+                    return;
+                }
+            }
+            Err(e) => {
+                warn!("Batch row {} failed: {}", entry.row, e);
+                entry.error = Some(e.to_string());
+            }
+        }
+
+        manifest.push(entry);
+    }
+
+    let data = serde_json::to_vec_pretty(&manifest).unwrap_or_default();
+    let _ = files.send(ArchiveFile { name: MANIFEST_FILE.to_string(), data }).await;
+}
+
+fn start_render(
+    pool: &Arc<RenderPool>,
+    row: BatchRow,
+    settings: &RenderSettings,
+    now: DateTime<Tz>,
+    issued: Option<Arc<IssuedRegistry>>,
+) -> (ManifestEntry, JoinHandle<Result<GeneratedImage, GenerateError>>) {
+    let BatchRow { row, template, generator, request } = row;
+    let entry = ManifestEntry {
+        row,
+        name: request.name.clone(),
+        template,
+        file: None,
+        seed: None,
+        format: None,
+        render_id: None,
+        error: None,
+    };
+
+    let options = settings.options_at(now, &request, OutputFormat::default());
+
+    let pool = pool.clone();
+    let job = tokio::spawn(async move {
+        pool.run_queued(move || {
+            let image = generator.generate_image(&request.render_request(), &options)?;
+            if let Some(issued) = issued {
+                issued.record(generator.manifest().id(), options.rendered_at, &image);
+            }
+            Ok(image)
+        })
+            .await
+            .map_err(|e| GenerateError::GenerationError(e.to_string()))?
+    });
+
+    (entry, job)
+}
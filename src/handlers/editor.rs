@@ -0,0 +1,156 @@
+use axum::{
+    body::Bytes,
+    response::{IntoResponse, Response},
+    Json,
+};
+use std::sync::Arc;
+
+use epovistka_core::{output::OutputFormat, template_registry::MANIFEST_FILE};
+
+use crate::{
+    config::RenderSettings,
+    handlers::generate::RENDER_SEED_HEADER,
+    models::{
+        editor::{AssetList, DraftRequest, EditorError, SaveResponse},
+        generate::GenerateRequest,
+        layout::LayoutResponse,
+    },
+    services::{
+        render_pool::{PoolError, RenderPool},
+        template_store::TemplateStore,
+    },
+};
+
+#[derive(Clone)]
+pub struct EditorHandler {
+    store: Arc<TemplateStore>,
+    settings: RenderSettings,
+    pool: Arc<RenderPool>,
+}
+
+impl EditorHandler {
+    pub fn new(store: Arc<TemplateStore>, settings: RenderSettings, pool: Arc<RenderPool>) -> Self {
+        Self { store, settings, pool }
+    }
+
+    pub async fn handle_manifest_request(&self, id: String) -> Result<Response, EditorError> {
+        let store = self.store.clone();
+        let manifest = blocking(move || store.manifest(&id)).await?;
+
+        Ok(([(http::header::CONTENT_TYPE, "application/json")], manifest).into_response())
+    }
+
+    /// Stores a manifest once it loads and renders; the template is served
+    /// from then on. Checking renders a document, so it runs on the render pool.
+    pub async fn handle_save_manifest_request(
+        &self,
+        id: String,
+        manifest: serde_json::Value,
+    ) -> Result<Json<SaveResponse>, EditorError> {
+        let store = self.store.clone();
+        let template = id.clone();
+        self.render(move || store.save_manifest(&template, &manifest)).await?;
+
+        Ok(Json(SaveResponse { template: id, file: MANIFEST_FILE.to_string(), success: true }))
+    }
+
+    /// Renders a request with an unsaved manifest and the template's stored
+    /// assets. Nothing is written or published, and the render is not
+    /// recorded as issued.
+    pub async fn handle_preview_request(&self, id: String, draft: DraftRequest) -> Result<Response, EditorError> {
+        let request = checked(draft.request)?;
+        let options = self.settings.options(&request, OutputFormat::default());
+        let store = self.store.clone();
+        let image = self
+            .render(move || {
+                let generator = store.draft(&id, draft.manifest)?;
+                Ok(generator.generate_image(&request.render_request(), &options)?)
+            })
+            .await?;
+
+        let headers = [(http::header::CONTENT_TYPE, image.format.content_type().to_string())];
+        Ok((headers, [(RENDER_SEED_HEADER, image.seed.to_string())], image.data).into_response())
+    }
+
+    /// Like [`Self::handle_preview_request`], with the text layout drawn over
+    /// the render as `/debug/layout` does.
+    pub async fn handle_layout_request(
+        &self,
+        id: String,
+        draft: DraftRequest,
+    ) -> Result<Json<LayoutResponse>, EditorError> {
+        let request = checked(draft.request)?;
+        let options = self.settings.options(&request, OutputFormat::Png);
+        let store = self.store.clone();
+        let template = id.clone();
+        let report = self
+            .render(move || {
+                let generator = store.draft(&template, draft.manifest)?;
+                Ok(generator.debug_layout(&request.render_request(), &options)?)
+            })
+            .await?;
+
+        Ok(Json(LayoutResponse::new(id, report)))
+    }
+
+    pub async fn handle_assets_request(&self, id: String) -> Result<Json<AssetList>, EditorError> {
+        let store = self.store.clone();
+        let template = id.clone();
+        let assets = blocking(move || store.assets(&template)).await?;
+
+        Ok(Json(AssetList { template: id, assets, success: true }))
+    }
+
+    pub async fn handle_asset_request(&self, id: String, name: String) -> Result<Response, EditorError> {
+        let store = self.store.clone();
+        let content_type = mime_guess::from_path(&name).first_or_octet_stream().to_string();
+        let data = blocking(move || store.asset(&id, &name)).await?;
+
+        Ok(([(http::header::CONTENT_TYPE, content_type)], data).into_response())
+    }
+
+    /// Stores an uploaded image or font. Decoding it to check it is as heavy
+    /// as rendering, so it runs on the render pool.
+    pub async fn handle_save_asset_request(
+        &self,
+        id: String,
+        name: String,
+        body: Bytes,
+    ) -> Result<Json<SaveResponse>, EditorError> {
+        let store = self.store.clone();
+        let (template, file) = (id.clone(), name.clone());
+        self.render(move || store.save_asset(&template, &file, &body)).await?;
+
+        Ok(Json(SaveResponse { template: id, file: name, success: true }))
+    }
+
+    async fn render<T, F>(&self, job: F) -> Result<T, EditorError>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> Result<T, EditorError> + Send + 'static,
+    {
+        self.pool
+            .run(job)
+            .await
+            .map_err(|e| match e {
+                PoolError::Overloaded => EditorError::Overloaded,
+                PoolError::Failed => EditorError::Failed(e.to_string()),
+            })?
+    }
+}
+
+fn checked(mut request: GenerateRequest) -> Result<GenerateRequest, EditorError> {
+    request.sanitize();
+    request.validate().map_err(|e| EditorError::ValidationError(e.to_string()))?;
+    Ok(request)
+}
+
+async fn blocking<T, F>(job: F) -> Result<T, EditorError>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, EditorError> + Send + 'static,
+{
+    tokio::task::spawn_blocking(job)
+        .await
+        .map_err(|e| EditorError::Failed(e.to_string()))?
+}
@@ -4,49 +4,87 @@ use axum::{
 use std::sync::Arc;
 use tracing::info;
 
+use epovistka_core::{output::OutputFormat, template_registry::SharedRegistry};
+
 use crate::{
+    config::RenderSettings,
     models::generate::{GenerateRequest, GenerateError},
-    services::image_generator::ImageGenerator,
+    services::{
+        issued_registry::IssuedRegistry,
+        render_pool::{PoolError, RenderPool},
+    },
 };
 
+pub const RENDER_SEED_HEADER: &str = "x-render-seed";
+pub const RENDER_ID_HEADER: &str = "x-render-id";
+
 #[derive(Clone)]
 pub struct GenerateImageHandler {
-    image_generator: Arc<ImageGenerator>,
+    registry: SharedRegistry,
+    settings: RenderSettings,
+    pool: Arc<RenderPool>,
+    issued: Option<Arc<IssuedRegistry>>,
 }
 
 impl GenerateImageHandler {
-    pub fn new() -> Result<Self, GenerateError> {
-        let image_generator = ImageGenerator::new()
-            .map_err(|e| GenerateError::InitializationError(e.to_string()))?;
-
-        Ok(Self {
-            image_generator: Arc::new(image_generator),
-        })
+    pub fn new(
+        registry: SharedRegistry,
+        settings: RenderSettings,
+        pool: Arc<RenderPool>,
+        issued: Option<Arc<IssuedRegistry>>,
+    ) -> Self {
+        Self { registry, settings, pool, issued }
     }
 
     pub async fn handle_generate_request(
         &self,
         mut request: GenerateRequest,
+        accept: Option<&str>,
     ) -> Result<Response, GenerateError> {
         request.sanitize();
         request.validate()?;
 
         info!("Processing generate request for: {}", request.name);
 
-        let image_data = self.image_generator.generate_image(&request).await?;
+        let image_generator = self.registry.load().get(request.template.as_deref())?;
+        let options = self
+            .settings
+            .options(&request, accept.map(OutputFormat::negotiate).unwrap_or_default());
+        let issued = self.issued.clone();
+        let image = self
+            .pool
+            .run(move || {
+                let image = image_generator.generate_image(&request.render_request(), &options)?;
+                if let Some(issued) = issued {
+                    issued.record(image_generator.manifest().id(), options.rendered_at, &image);
+                }
+                Ok::<_, GenerateError>(image)
+            })
+            .await
+            .map_err(|e| match e {
+                PoolError::Overloaded => GenerateError::Overloaded,
+                PoolError::Failed => GenerateError::GenerationError(e.to_string()),
+            })??;
 
         let headers = [
-            (http::header::CONTENT_TYPE, "image/png"),
+            (http::header::CONTENT_TYPE, image.format.content_type().to_string()),
             (
                 http::header::CONTENT_DISPOSITION,
-                "inline; filename=\"povistka.png\"",
+                format!("inline; filename=\"povistka.{}\"", image.format.extension()),
             ),
             (
                 http::header::CACHE_CONTROL,
-                "no-cache, no-store, must-revalidate",
+                "no-cache, no-store, must-revalidate".to_string(),
             ),
+            // Without a `format` field the encoding follows the Accept header.
+            (http::header::VARY, "accept".to_string()),
+        ];
+
+        let render_headers = [
+            (RENDER_SEED_HEADER, image.seed.to_string()),
+            (RENDER_ID_HEADER, image.render_id),
         ];
 
-        Ok((headers, image_data).into_response())
+        Ok((headers, render_headers, image.data).into_response())
     }
 }
@@ -0,0 +1,51 @@
+use axum::Json;
+use std::sync::Arc;
+use tracing::info;
+
+use epovistka_core::{output::OutputFormat, template_registry::SharedRegistry};
+
+use crate::{
+    config::RenderSettings,
+    models::{
+        generate::{GenerateError, GenerateRequest},
+        layout::LayoutResponse,
+    },
+    services::render_pool::{PoolError, RenderPool},
+};
+
+#[derive(Clone)]
+pub struct LayoutHandler {
+    registry: SharedRegistry,
+    settings: RenderSettings,
+    pool: Arc<RenderPool>,
+}
+
+impl LayoutHandler {
+    pub fn new(registry: SharedRegistry, settings: RenderSettings, pool: Arc<RenderPool>) -> Self {
+        Self { registry, settings, pool }
+    }
+
+    /// Renders a request with the template's text layout drawn over it, so
+    /// template authors can check their coordinates.
+    pub async fn handle_layout_request(&self, mut request: GenerateRequest) -> Result<Json<LayoutResponse>, GenerateError> {
+        request.sanitize();
+        request.validate()?;
+
+        let generator = self.registry.load().get(request.template.as_deref())?;
+        let options = self.settings.options(&request, OutputFormat::Png);
+        let template = generator.manifest().id().to_string();
+
+        let report = self
+            .pool
+            .run(move || generator.debug_layout(&request.render_request(), &options))
+            .await
+            .map_err(|e| match e {
+                PoolError::Overloaded => GenerateError::Overloaded,
+                PoolError::Failed => GenerateError::GenerationError(e.to_string()),
+            })??;
+
+        info!("Rendered layout of template {} with {} issues", template, report.issues.len());
+
+        Ok(Json(LayoutResponse::new(template, report)))
+    }
+}
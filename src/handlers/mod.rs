@@ -1 +1,7 @@
-pub mod generate;
\ No newline at end of file
+pub mod batch;
+pub mod editor;
+pub mod generate;
+pub mod layout;
+pub mod registry;
+pub mod templates;
+pub mod verify;
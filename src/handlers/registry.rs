@@ -0,0 +1,87 @@
+use axum::{body::Bytes, Json};
+use chrono::Utc;
+use std::sync::Arc;
+use tracing::info;
+
+use epovistka_core::perceptual_hash::PerceptualHash;
+
+use crate::{
+    models::registry::{FlagRequest, LookupResponse, RegistryError, MAX_REASON_LENGTH},
+    services::{
+        clock::Clock,
+        issued_registry::IssuedRegistry,
+        render_pool::{PoolError, RenderPool},
+        upload,
+    },
+};
+
+#[derive(Clone)]
+pub struct RegistryHandler {
+    issued: Option<Arc<IssuedRegistry>>,
+    clock: Arc<dyn Clock>,
+    pool: Arc<RenderPool>,
+}
+
+impl RegistryHandler {
+    pub fn new(issued: Option<Arc<IssuedRegistry>>, clock: Arc<dyn Clock>, pool: Arc<RenderPool>) -> Self {
+        Self { issued, clock, pool }
+    }
+
+    /// Hashes an uploaded image and lists the registered renders it resembles.
+    pub async fn handle_lookup_request(&self, body: Bytes) -> Result<Json<LookupResponse>, RegistryError> {
+        let issued = self.issued.clone().ok_or(RegistryError::Disabled)?;
+
+        // Comparing text regions means hashing parts of the upload too, so the
+        // lookup runs on the render pool along with decoding.
+        let (hash, matches) = self
+            .pool
+            .run(move || {
+                let image = upload::decode_image(&body).map_err(RegistryError::InvalidImage)?.to_luma8();
+                let hash = PerceptualHash::of(&image);
+                let matches = issued.lookup(&image, &hash);
+                Ok((hash, matches))
+            })
+            .await
+            .map_err(|e| match e {
+                PoolError::Overloaded => RegistryError::Overloaded,
+                PoolError::Failed => RegistryError::Failed(e.to_string()),
+            })??;
+
+        info!("Registry lookup found {} matches", matches.len());
+
+        Ok(Json(LookupResponse { hash, matches, success: true }))
+    }
+
+    pub async fn handle_flag_request(
+        &self,
+        render_id: &str,
+        request: FlagRequest,
+    ) -> Result<Json<serde_json::Value>, RegistryError> {
+        let issued = self.issued.clone().ok_or(RegistryError::Disabled)?;
+
+        let reason = request.reason.trim().to_string();
+        if reason.is_empty() {
+            return Err(RegistryError::ValidationError("Reason cannot be empty".to_string()));
+        }
+        if reason.chars().count() > MAX_REASON_LENGTH {
+            return Err(RegistryError::ValidationError(format!(
+                "Reason is too long (max {} characters)",
+                MAX_REASON_LENGTH
+            )));
+        }
+
+        let flagged_at = self.clock.now().with_timezone(&Utc);
+        let render = render_id.to_string();
+        let found = tokio::task::spawn_blocking(move || issued.flag(&render, reason, flagged_at))
+            .await
+            .map_err(|e| RegistryError::Failed(e.to_string()))?
+            .map_err(|e| RegistryError::Failed(e.to_string()))?;
+
+        if !found {
+            return Err(RegistryError::UnknownRender(render_id.to_string()));
+        }
+
+        info!("Flagged render {}", render_id);
+        Ok(Json(serde_json::json!({ "render_id": render_id, "success": true })))
+    }
+}
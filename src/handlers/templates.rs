@@ -0,0 +1,48 @@
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+
+use epovistka_core::template_registry::SharedRegistry;
+
+use crate::models::templates::TemplateSummary;
+
+#[derive(Clone)]
+pub struct TemplatesHandler {
+    registry: SharedRegistry,
+}
+
+impl TemplatesHandler {
+    pub fn new(registry: SharedRegistry) -> Self {
+        Self { registry }
+    }
+
+    pub fn list_templates(&self) -> Json<Vec<TemplateSummary>> {
+        let registry = self.registry.load();
+        let summaries = registry
+            .entries()
+            .map(|(id, entry)| {
+                let manifest = entry.generator.manifest();
+                TemplateSummary {
+                    id: id.clone(),
+                    name: manifest.name.clone(),
+                    preview: format!("/templates/{}/preview", id),
+                    required_fields: manifest.required_fields(),
+                    locale: manifest.locale,
+                    default: id == registry.default_id(),
+                }
+            })
+            .collect();
+
+        Json(summaries)
+    }
+
+    pub fn preview(&self, id: &str) -> Result<Response, StatusCode> {
+        let registry = self.registry.load();
+        let entry = registry.entry(id).ok_or(StatusCode::NOT_FOUND)?;
+
+        // Not cacheable: previews change whenever the template is reloaded or edited.
+        Ok(([(http::header::CONTENT_TYPE, "image/png")], entry.preview.as_ref().clone()).into_response())
+    }
+}
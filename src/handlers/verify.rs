@@ -0,0 +1,52 @@
+use axum::{body::Bytes, Json};
+use std::sync::Arc;
+use tracing::info;
+
+use epovistka_core::fingerprint::Fingerprint;
+
+use crate::{
+    models::verify::{VerifyError, VerifyResponse},
+    services::{
+        render_pool::{PoolError, RenderPool},
+        upload,
+    },
+};
+
+#[derive(Clone)]
+pub struct VerifyHandler {
+    fingerprint: Option<Arc<Fingerprint>>,
+    pool: Arc<RenderPool>,
+}
+
+impl VerifyHandler {
+    pub fn new(fingerprint: Option<Arc<Fingerprint>>, pool: Arc<RenderPool>) -> Self {
+        Self { fingerprint, pool }
+    }
+
+    /// Decodes an uploaded image and looks for the fingerprint in it. Decoding
+    /// is as CPU-heavy as rendering, so it runs on the render pool.
+    pub async fn handle_verify_request(&self, body: Bytes) -> Result<Json<VerifyResponse>, VerifyError> {
+        let fingerprint = self.fingerprint.clone().ok_or(VerifyError::Disabled)?;
+
+        let detection = self
+            .pool
+            .run(move || {
+                let image = upload::decode_image(&body).map_err(VerifyError::InvalidImage)?;
+                Ok(fingerprint.detect(&image.to_luma8()))
+            })
+            .await
+            .map_err(|e| match e {
+                PoolError::Overloaded => VerifyError::Overloaded,
+                PoolError::Failed => VerifyError::Failed,
+            })??;
+
+        info!("Verified image: score {:.2}", detection.score);
+
+        Ok(Json(VerifyResponse {
+            generated: detection.detected(),
+            confidence: detection.confidence,
+            score: detection.score,
+            success: true,
+        }))
+    }
+}
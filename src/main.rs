@@ -1,4 +1,5 @@
 use axum::{
+    extract::DefaultBodyLimit,
     routing::{get, post},
     Router,
 };
@@ -8,33 +9,152 @@ use tower_http::{
     trace::TraceLayer,
     set_header::SetResponseHeaderLayer,
 };
-use tracing_subscriber;
 use http::HeaderValue;
 
+mod cli;
+mod config;
 mod routes;
 mod handlers;
 mod models;
 mod services;
 mod middleware;
+mod state;
 
-use routes::{generate, static_files};
-use handlers::generate::GenerateImageHandler;
+use routes::{batch, generate, static_files, templates, verify};
+use handlers::{
+    batch::BatchHandler, editor::EditorHandler, generate::GenerateImageHandler, layout::LayoutHandler, registry::RegistryHandler,
+    templates::TemplatesHandler, verify::VerifyHandler,
+};
+use middleware::admin;
+use models::verify::MAX_UPLOAD_BYTES;
+use arc_swap::ArcSwap;
+use clap::Parser;
+use cli::{Cli, Command};
+use config::Config;
+use epovistka_core::template_registry::TemplateRegistry;
+use services::{issued_registry::IssuedRegistry, render_pool::RenderPool, template_store::TemplateStore, template_watcher};
+use state::AppState;
 use std::sync::Arc;
 
+fn main() {
+    let cli = Cli::parse();
+    let mut config = Config::from_env().expect("Invalid configuration");
+    if let Some(dir) = cli.templates_dir {
+        config.templates_dir = dir;
+    }
+
+    match cli.command.unwrap_or(Command::Serve) {
+        Command::Serve => {
+            tracing_subscriber::fmt::init();
+            serve(config);
+        }
+        Command::Render(args) => {
+            // Documents can go to standard output, so logs keep to standard error
+            tracing_subscriber::fmt()
+                .with_writer(std::io::stderr)
+                .with_max_level(tracing::Level::WARN)
+                .init();
+            match cli::render(config, *args) {
+                Ok(true) => {}
+                Ok(false) => std::process::exit(1),
+                Err(e) => {
+                    eprintln!("error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+    }
+}
+
 #[tokio::main]
-async fn main() {
-    tracing_subscriber::fmt::init();
+async fn serve(config: Config) {
+    let registry = Arc::new(ArcSwap::from_pointee(
+        TemplateRegistry::load(&config.templates_dir).expect("Failed to load templates")
+    ));
+
+    if config.hot_reload {
+        if let Err(e) = template_watcher::spawn(config.templates_dir.clone(), registry.clone()) {
+            tracing::warn!("Template hot reload disabled: {}", e);
+        }
+    }
 
-    let generate_handler = Arc::new(
-        GenerateImageHandler::new().expect("Failed to create generate handler")
+    let pool = Arc::new(RenderPool::new(config.render_workers, config.render_queue));
+    if config.fingerprint.is_none() {
+        tracing::warn!("EPOVISTKA_FINGERPRINT_SECRET is not set, documents are not fingerprinted");
+    }
+    tracing::info!(
+        "Rendering on {} workers with a queue of {}",
+        config.render_workers,
+        config.render_queue
     );
 
+    let issued = config.registry_path.as_ref().map(|path| {
+        Arc::new(IssuedRegistry::open(path, config.registry_capacity).expect("Failed to open the image registry"))
+    });
+
+    let state = AppState {
+        generate: Arc::new(GenerateImageHandler::new(
+            registry.clone(),
+            config.render_settings(),
+            pool.clone(),
+            issued.clone(),
+        )),
+        batch: Arc::new(BatchHandler::new(
+            registry.clone(),
+            config.render_settings(),
+            pool.clone(),
+            issued.clone(),
+        )),
+        layout: Arc::new(LayoutHandler::new(registry.clone(), config.render_settings(), pool.clone())),
+        editor: Arc::new(EditorHandler::new(
+            Arc::new(TemplateStore::new(config.templates_dir.clone(), registry.clone())),
+            config.render_settings(),
+            pool.clone(),
+        )),
+        templates: Arc::new(TemplatesHandler::new(registry)),
+        verify: Arc::new(VerifyHandler::new(config.fingerprint.clone(), pool.clone())),
+        registry: Arc::new(RegistryHandler::new(issued, config.clock(), pool)),
+    };
+
+    let admin_routes = Router::new()
+        .route(
+            "/admin/registry/lookup",
+            post(routes::registry::lookup_image).layer(DefaultBodyLimit::max(MAX_UPLOAD_BYTES)),
+        )
+        .route("/admin/registry/{render_id}/flag", post(routes::registry::flag_render))
+        .route("/debug/layout", post(routes::layout::debug_layout))
+        .route(
+            "/admin/templates/{id}/manifest",
+            get(routes::editor::get_manifest).put(routes::editor::save_manifest),
+        )
+        .route("/admin/templates/{id}/preview", post(routes::editor::preview_draft))
+        .route("/admin/templates/{id}/layout", post(routes::editor::check_draft_layout))
+        .route("/admin/templates/{id}/assets", get(routes::editor::list_assets))
+        .route(
+            "/admin/templates/{id}/assets/{name}",
+            get(routes::editor::get_asset)
+                .put(routes::editor::save_asset)
+                .layer(DefaultBodyLimit::max(MAX_UPLOAD_BYTES)),
+        )
+        .route_layer(axum::middleware::from_fn_with_state(
+            config.admin_token.clone(),
+            admin::require_admin,
+        ));
+
     let app = Router::new()
         .route("/", get(static_files::serve_index))
         .route("/generate", post(generate::generate_image))
+        .route("/batch", post(batch::generate_batch))
+        .route(
+            "/verify",
+            post(verify::verify_image).layer(DefaultBodyLimit::max(MAX_UPLOAD_BYTES)),
+        )
+        .route("/templates", get(templates::list_templates))
+        .route("/templates/{id}/preview", get(templates::template_preview))
         .route("/static/{*path}", get(static_files::serve_static_files))
+        .merge(admin_routes)
         .fallback(static_files::serve_index)
-        .with_state(generate_handler)
+        .with_state(state)
         .layer(TraceLayer::new_for_http())
         .layer(CompressionLayer::new())
         .layer(SetResponseHeaderLayer::overriding(
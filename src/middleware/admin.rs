@@ -0,0 +1,65 @@
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::sync::Arc;
+
+/// Bearer token that unlocks the admin endpoints; `None` disables them.
+#[derive(Clone, Default)]
+pub struct AdminToken(Option<Arc<str>>);
+
+impl AdminToken {
+    pub fn new(token: Option<String>) -> Self {
+        Self(token.map(Arc::from))
+    }
+
+    fn accepts(&self, presented: &str) -> bool {
+        match &self.0 {
+            Some(token) => constant_time_eq(token.as_bytes(), presented.as_bytes()),
+            None => false,
+        }
+    }
+}
+
+// Keep the token out of logs.
+impl std::fmt::Debug for AdminToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("AdminToken").field(&self.0.as_ref().map(|_| "..")).finish()
+    }
+}
+
+/// Lets a request through only with `Authorization: Bearer <token>`.
+pub async fn require_admin(State(token): State<AdminToken>, request: Request, next: Next) -> Response {
+    if token.0.is_none() {
+        return reject(http::StatusCode::NOT_FOUND, "Admin endpoints are disabled");
+    }
+
+    let presented = request
+        .headers()
+        .get(http::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    match presented {
+        Some(presented) if token.accepts(presented.trim()) => next.run(request).await,
+        _ => reject(http::StatusCode::UNAUTHORIZED, "Admin token required"),
+    }
+}
+
+fn reject(status: http::StatusCode, message: &str) -> Response {
+    let body = axum::Json(serde_json::json!({
+        "error": message,
+        "success": false
+    }));
+    (status, body).into_response()
+}
+
+/// Compares without returning early, so response times do not reveal how
+/// much of a guessed token was right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0, |difference, (x, y)| difference | (x ^ y)) == 0
+}
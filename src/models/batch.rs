@@ -0,0 +1,123 @@
+use axum::response::IntoResponse;
+use serde::Serialize;
+use thiserror::Error;
+
+use epovistka_core::output::OutputFormat;
+
+use crate::models::generate::GenerateRequest;
+
+/// Most documents a single batch may ask for.
+pub const MAX_BATCH_ROWS: usize = 100;
+
+/// Body formats `/batch` accepts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatchFormat {
+    Csv,
+    Json,
+}
+
+impl BatchFormat {
+    /// Picks the format from the `Content-Type` header, falling back to
+    /// sniffing the body when the header is missing or generic.
+    pub fn detect(content_type: Option<&str>, body: &[u8]) -> Self {
+        let media_type = content_type
+            .and_then(|value| value.split(';').next())
+            .map(|value| value.trim().to_ascii_lowercase());
+
+        match media_type.as_deref() {
+            Some("application/json") => BatchFormat::Json,
+            Some("text/csv") => BatchFormat::Csv,
+            _ if body.trim_ascii_start().starts_with(b"[") => BatchFormat::Json,
+            _ => BatchFormat::Csv,
+        }
+    }
+}
+
+/// Parses a batch body into one request per row. Rows are numbered from 1;
+/// in CSV the header line does not count.
+pub fn parse_rows(format: BatchFormat, body: &[u8]) -> Result<Vec<Result<GenerateRequest, String>>, BatchError> {
+    let rows: Vec<Result<GenerateRequest, String>> = match format {
+        BatchFormat::Json => {
+            let values: Vec<serde_json::Value> = serde_json::from_slice(body)
+                .map_err(|e| BatchError::InvalidBody(format!("Expected a JSON array of requests: {}", e)))?;
+            values
+                .into_iter()
+                .map(|value| serde_json::from_value(value).map_err(|e| e.to_string()))
+                .collect()
+        }
+        BatchFormat::Csv => csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(body)
+            .deserialize()
+            .map(|row| row.map_err(|e| e.to_string()))
+            .collect(),
+    };
+
+    if rows.is_empty() {
+        return Err(BatchError::InvalidBody("Batch contains no rows".to_string()));
+    }
+    if rows.len() > MAX_BATCH_ROWS {
+        return Err(BatchError::InvalidBody(format!(
+            "Batch has {} rows, at most {} are allowed",
+            rows.len(),
+            MAX_BATCH_ROWS
+        )));
+    }
+
+    Ok(rows)
+}
+
+#[derive(Debug, Serialize)]
+pub struct RowError {
+    pub row: usize,
+    pub error: String,
+}
+
+/// Entry of the archive's `manifest.json`, one per input row.
+#[derive(Debug, Serialize)]
+pub struct ManifestEntry {
+    pub row: usize,
+    pub name: String,
+    pub template: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<OutputFormat>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub render_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Error, Debug)]
+pub enum BatchError {
+    #[error("Invalid batch: {0}")]
+    InvalidBody(String),
+
+    #[error("{} of the batch rows are invalid", .0.len())]
+    InvalidRows(Vec<RowError>),
+}
+
+impl IntoResponse for BatchError {
+    fn into_response(self) -> axum::response::Response {
+        match self {
+            BatchError::InvalidBody(msg) => {
+                let body = axum::Json(serde_json::json!({
+                    "error": msg,
+                    "success": false
+                }));
+                (http::StatusCode::BAD_REQUEST, body).into_response()
+            }
+            BatchError::InvalidRows(ref rows) => {
+                let body = axum::Json(serde_json::json!({
+                    "error": self.to_string(),
+                    "rows": rows,
+                    "success": false
+                }));
+                (http::StatusCode::BAD_REQUEST, body).into_response()
+            }
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use axum::response::IntoResponse;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use epovistka_core::error::RenderError;
+
+use crate::models::generate::{GenerateRequest, RETRY_AFTER_SECONDS};
+
+#[derive(Debug, Serialize)]
+pub struct AssetList {
+    pub template: String,
+    /// File names of the template's images and fonts.
+    pub assets: Vec<String>,
+    pub success: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SaveResponse {
+    pub template: String,
+    /// The saved file, relative to the template's directory.
+    pub file: String,
+    pub success: bool,
+}
+
+/// An unsaved manifest and the request to render it with.
+#[derive(Debug, Deserialize)]
+pub struct DraftRequest {
+    pub manifest: serde_json::Value,
+    pub request: GenerateRequest,
+}
+
+#[derive(Error, Debug)]
+pub enum EditorError {
+    #[error("{0}")]
+    ValidationError(String),
+
+    #[error("Template rejected: {0}")]
+    InvalidManifest(String),
+
+    #[error("Not found: {0}")]
+    NotFound(String),
+
+    #[error("Server is busy, try again later")]
+    Overloaded,
+
+    #[error("Template operation failed: {0}")]
+    Failed(String),
+}
+
+impl From<RenderError> for EditorError {
+    fn from(error: RenderError) -> Self {
+        match error {
+            RenderError::InvalidValue(_) => EditorError::ValidationError(error.to_string()),
+            RenderError::UnknownTemplate(_) => EditorError::NotFound(error.to_string()),
+            RenderError::Failed(msg) => EditorError::Failed(msg),
+            RenderError::Template(_) | RenderError::Io { .. } | RenderError::Image { .. } | RenderError::Font(_) => {
+                EditorError::InvalidManifest(error.to_string())
+            }
+        }
+    }
+}
+
+impl IntoResponse for EditorError {
+    fn into_response(self) -> axum::response::Response {
+        let status = match self {
+            EditorError::ValidationError(_) => http::StatusCode::BAD_REQUEST,
+            EditorError::InvalidManifest(_) => http::StatusCode::UNPROCESSABLE_ENTITY,
+            EditorError::NotFound(_) => http::StatusCode::NOT_FOUND,
+            EditorError::Overloaded => http::StatusCode::SERVICE_UNAVAILABLE,
+            EditorError::Failed(_) => http::StatusCode::INTERNAL_SERVER_ERROR,
+        };
+
+        let body = axum::Json(serde_json::json!({
+            "error": self.to_string(),
+            "success": false
+        }));
+
+        let mut response = (status, body).into_response();
+        if status == http::StatusCode::SERVICE_UNAVAILABLE {
+            response
+                .headers_mut()
+                .insert(http::header::RETRY_AFTER, RETRY_AFTER_SECONDS.into());
+        }
+
+        response
+    }
+}
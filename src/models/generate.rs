@@ -1,11 +1,45 @@
 use axum::response::IntoResponse;
+use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+use epovistka_core::{
+    error::RenderError,
+    image_generator::RenderRequest,
+    locale::LocaleId,
+    output::{OutputFormat, OutputOptions},
+    template::PaperSize,
+};
+
 #[derive(Debug, Deserialize)]
 pub struct GenerateRequest {
     pub name: String,
     pub address: String,
+    #[serde(default)]
+    pub template: Option<String>,
+    /// Seed for every random choice in the render; the same request and seed
+    /// produce the same document, and a byte-identical file when the clock is
+    /// fixed with `EPOVISTKA_FIXED_TIME`.
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// Document date (`YYYY-MM-DD`); defaults to today in the configured timezone.
+    #[serde(default)]
+    pub date: Option<NaiveDate>,
+    /// Overrides the template's locale for month names and number formats.
+    #[serde(default)]
+    pub locale: Option<LocaleId>,
+    /// Output encoding; takes precedence over the `Accept` header.
+    #[serde(default)]
+    pub format: Option<OutputFormat>,
+    /// Quality (1-100) for lossy formats; ignored by PNG and lossless WebP.
+    #[serde(default)]
+    pub quality: Option<u8>,
+    /// Paper size for PDF output; defaults to the one the template declares.
+    #[serde(default)]
+    pub paper: Option<PaperSize>,
+    /// Turns the parody watermark on or off, unless the server makes it mandatory.
+    #[serde(default)]
+    pub watermark: Option<bool>,
 }
 
 impl GenerateRequest {
@@ -26,9 +60,32 @@ impl GenerateRequest {
             return Err(GenerateError::ValidationError("Address is too long".to_string()));
         }
 
+        if self.quality.is_some_and(|quality| !(1..=100).contains(&quality)) {
+            return Err(GenerateError::ValidationError("Quality must be between 1 and 100".to_string()));
+        }
+
         Ok(())
     }
 
+    /// Encoding settings of the request; `fallback` applies when it names no format.
+    pub fn output_options(&self, fallback: OutputFormat) -> OutputOptions {
+        OutputOptions {
+            format: self.format.unwrap_or(fallback),
+            quality: self.quality,
+            paper: self.paper,
+        }
+    }
+
+    /// The values the renderer draws into the document.
+    pub fn render_request(&self) -> RenderRequest {
+        RenderRequest {
+            name: self.name.clone(),
+            address: self.address.clone(),
+            seed: self.seed,
+            locale: self.locale,
+        }
+    }
+
     pub fn sanitize(&mut self) {
         self.name = self.name.trim().to_string();
         self.address = self.address.trim().to_string();
@@ -53,17 +110,39 @@ pub enum GenerateError {
     #[error("Initialization error: {0}")]
     InitializationError(String),
 
+    #[error("Server is busy, try again later")]
+    Overloaded,
+
     #[allow(dead_code)]
     #[error("Invalid input data")]
     InvalidInput,
 }
 
+impl From<RenderError> for GenerateError {
+    fn from(error: RenderError) -> Self {
+        match error {
+            RenderError::UnknownTemplate(_) | RenderError::InvalidValue(_) => {
+                GenerateError::ValidationError(error.to_string())
+            }
+            RenderError::Failed(msg) => GenerateError::GenerationError(msg),
+            RenderError::Template(_)
+            | RenderError::Io { .. }
+            | RenderError::Image { .. }
+            | RenderError::Font(_) => GenerateError::GenerationError(error.to_string()),
+        }
+    }
+}
+
+/// Seconds clients are asked to wait when the render queue is full.
+pub const RETRY_AFTER_SECONDS: u64 = 2;
+
 impl IntoResponse for GenerateError {
     fn into_response(self) -> axum::response::Response {
         let (status, error_message) = match self {
             GenerateError::ValidationError(msg) => (http::StatusCode::BAD_REQUEST, msg),
             GenerateError::GenerationError(msg) => (http::StatusCode::INTERNAL_SERVER_ERROR, msg),
             GenerateError::InitializationError(msg) => (http::StatusCode::INTERNAL_SERVER_ERROR, msg),
+            GenerateError::Overloaded => (http::StatusCode::SERVICE_UNAVAILABLE, self.to_string()),
             GenerateError::InvalidInput => (http::StatusCode::BAD_REQUEST, "Invalid input data".to_string()),
         };
 
@@ -72,6 +151,13 @@ impl IntoResponse for GenerateError {
             "success": false
         }));
 
-        (status, body).into_response()
+        let mut response = (status, body).into_response();
+        if status == http::StatusCode::SERVICE_UNAVAILABLE {
+            response
+                .headers_mut()
+                .insert(http::header::RETRY_AFTER, RETRY_AFTER_SECONDS.into());
+        }
+
+        response
     }
 }
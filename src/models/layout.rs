@@ -0,0 +1,28 @@
+use serde::Serialize;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+use epovistka_core::{image_generator::LayoutReport, layout_debug::LayoutIssue};
+
+#[derive(Debug, Serialize)]
+pub struct LayoutResponse {
+    pub template: String,
+    pub seed: u64,
+    /// The render with its layout drawn over it, as a PNG data URL.
+    pub image: String,
+    /// Glyphs outside the image or their box; empty when the layout is clean.
+    pub issues: Vec<LayoutIssue>,
+    pub success: bool,
+}
+
+impl LayoutResponse {
+    pub fn new(template: String, report: LayoutReport) -> Self {
+        Self {
+            template,
+            seed: report.image.seed,
+            image: format!("data:{};base64,{}", report.image.format.content_type(), STANDARD.encode(&report.image.data)),
+            issues: report.issues,
+            success: true,
+        }
+    }
+}
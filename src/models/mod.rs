@@ -1 +1,7 @@
+pub mod batch;
+pub mod editor;
 pub mod generate;
+pub mod layout;
+pub mod registry;
+pub mod templates;
+pub mod verify;
@@ -0,0 +1,74 @@
+use axum::response::IntoResponse;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use epovistka_core::perceptual_hash::PerceptualHash;
+
+use crate::models::generate::RETRY_AFTER_SECONDS;
+use crate::services::issued_registry::Match;
+
+/// Longest flag reason kept.
+pub const MAX_REASON_LENGTH: usize = 500;
+
+#[derive(Debug, Serialize)]
+pub struct LookupResponse {
+    /// Hashes of the submitted image.
+    pub hash: PerceptualHash,
+    /// Registered renders the submitted image is a copy of, closest first.
+    /// Their text regions are compared as well as the whole page, so renders
+    /// of the same template with other text do not match.
+    pub matches: Vec<Match>,
+    pub success: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FlagRequest {
+    pub reason: String,
+}
+
+#[derive(Error, Debug)]
+pub enum RegistryError {
+    #[error("The image registry is not enabled on this server")]
+    Disabled,
+
+    #[error("Could not read the image: {0}")]
+    InvalidImage(String),
+
+    #[error("{0}")]
+    ValidationError(String),
+
+    #[error("Unknown render '{0}'")]
+    UnknownRender(String),
+
+    #[error("Server is busy, try again later")]
+    Overloaded,
+
+    #[error("Registry operation failed: {0}")]
+    Failed(String),
+}
+
+impl IntoResponse for RegistryError {
+    fn into_response(self) -> axum::response::Response {
+        let status = match self {
+            RegistryError::Disabled => http::StatusCode::NOT_IMPLEMENTED,
+            RegistryError::InvalidImage(_) | RegistryError::ValidationError(_) => http::StatusCode::BAD_REQUEST,
+            RegistryError::UnknownRender(_) => http::StatusCode::NOT_FOUND,
+            RegistryError::Overloaded => http::StatusCode::SERVICE_UNAVAILABLE,
+            RegistryError::Failed(_) => http::StatusCode::INTERNAL_SERVER_ERROR,
+        };
+
+        let body = axum::Json(serde_json::json!({
+            "error": self.to_string(),
+            "success": false
+        }));
+
+        let mut response = (status, body).into_response();
+        if status == http::StatusCode::SERVICE_UNAVAILABLE {
+            response
+                .headers_mut()
+                .insert(http::header::RETRY_AFTER, RETRY_AFTER_SECONDS.into());
+        }
+
+        response
+    }
+}
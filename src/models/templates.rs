@@ -0,0 +1,13 @@
+use serde::Serialize;
+
+use epovistka_core::{locale::LocaleId, template::RequestField};
+
+#[derive(Debug, Serialize)]
+pub struct TemplateSummary {
+    pub id: String,
+    pub name: String,
+    pub preview: String,
+    pub required_fields: Vec<RequestField>,
+    pub locale: LocaleId,
+    pub default: bool,
+}
@@ -0,0 +1,63 @@
+use axum::response::IntoResponse;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::models::generate::RETRY_AFTER_SECONDS;
+
+/// Largest image `/verify` and the registry lookup accept.
+pub const MAX_UPLOAD_BYTES: usize = 20 * 1024 * 1024;
+/// Largest width or height of an uploaded image. Documents are rendered at
+/// about 1000 x 1250 pixels, which leaves room for scans and photos of them.
+pub const MAX_UPLOAD_SIDE: u32 = 4096;
+/// Most memory decoding an upload may take, enough for RGBA at the largest size.
+pub const MAX_UPLOAD_ALLOC: u64 = 64 * 1024 * 1024;
+
+#[derive(Debug, Serialize)]
+pub struct VerifyResponse {
+    /// Whether the image carries this service's fingerprint.
+    pub generated: bool,
+    /// 0 to 1, see [`epovistka_core::fingerprint::Detection`].
+    pub confidence: f32,
+    pub score: f32,
+    pub success: bool,
+}
+
+#[derive(Error, Debug)]
+pub enum VerifyError {
+    #[error("Verification is not enabled on this server")]
+    Disabled,
+
+    #[error("Could not read the image: {0}")]
+    InvalidImage(String),
+
+    #[error("Server is busy, try again later")]
+    Overloaded,
+
+    #[error("Verification failed")]
+    Failed,
+}
+
+impl IntoResponse for VerifyError {
+    fn into_response(self) -> axum::response::Response {
+        let status = match self {
+            VerifyError::Disabled => http::StatusCode::NOT_IMPLEMENTED,
+            VerifyError::InvalidImage(_) => http::StatusCode::BAD_REQUEST,
+            VerifyError::Overloaded => http::StatusCode::SERVICE_UNAVAILABLE,
+            VerifyError::Failed => http::StatusCode::INTERNAL_SERVER_ERROR,
+        };
+
+        let body = axum::Json(serde_json::json!({
+            "error": self.to_string(),
+            "success": false
+        }));
+
+        let mut response = (status, body).into_response();
+        if status == http::StatusCode::SERVICE_UNAVAILABLE {
+            response
+                .headers_mut()
+                .insert(http::header::RETRY_AFTER, RETRY_AFTER_SECONDS.into());
+        }
+
+        response
+    }
+}
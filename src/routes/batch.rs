@@ -0,0 +1,21 @@
+use axum::{
+    body::Bytes,
+    extract::State,
+    response::Response,
+};
+use http::{header::CONTENT_TYPE, HeaderMap};
+use std::sync::Arc;
+
+use crate::{
+    handlers::batch::BatchHandler,
+    models::batch::BatchError,
+};
+
+pub async fn generate_batch(
+    State(handler): State<Arc<BatchHandler>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, BatchError> {
+    let content_type = headers.get(CONTENT_TYPE).and_then(|value| value.to_str().ok());
+    handler.handle_batch_request(content_type, &body)
+}
@@ -0,0 +1,68 @@
+use axum::{
+    body::Bytes,
+    extract::{Path, State},
+    response::Response,
+    Json,
+};
+use std::sync::Arc;
+
+use crate::{
+    handlers::editor::EditorHandler,
+    models::{
+        editor::{AssetList, DraftRequest, EditorError, SaveResponse},
+        layout::LayoutResponse,
+    },
+};
+
+pub async fn get_manifest(
+    State(handler): State<Arc<EditorHandler>>,
+    Path(id): Path<String>,
+) -> Result<Response, EditorError> {
+    handler.handle_manifest_request(id).await
+}
+
+pub async fn save_manifest(
+    State(handler): State<Arc<EditorHandler>>,
+    Path(id): Path<String>,
+    Json(manifest): Json<serde_json::Value>,
+) -> Result<Json<SaveResponse>, EditorError> {
+    handler.handle_save_manifest_request(id, manifest).await
+}
+
+pub async fn preview_draft(
+    State(handler): State<Arc<EditorHandler>>,
+    Path(id): Path<String>,
+    Json(draft): Json<DraftRequest>,
+) -> Result<Response, EditorError> {
+    handler.handle_preview_request(id, draft).await
+}
+
+pub async fn check_draft_layout(
+    State(handler): State<Arc<EditorHandler>>,
+    Path(id): Path<String>,
+    Json(draft): Json<DraftRequest>,
+) -> Result<Json<LayoutResponse>, EditorError> {
+    handler.handle_layout_request(id, draft).await
+}
+
+pub async fn list_assets(
+    State(handler): State<Arc<EditorHandler>>,
+    Path(id): Path<String>,
+) -> Result<Json<AssetList>, EditorError> {
+    handler.handle_assets_request(id).await
+}
+
+pub async fn get_asset(
+    State(handler): State<Arc<EditorHandler>>,
+    Path((id, name)): Path<(String, String)>,
+) -> Result<Response, EditorError> {
+    handler.handle_asset_request(id, name).await
+}
+
+pub async fn save_asset(
+    State(handler): State<Arc<EditorHandler>>,
+    Path((id, name)): Path<(String, String)>,
+    body: Bytes,
+) -> Result<Json<SaveResponse>, EditorError> {
+    handler.handle_save_asset_request(id, name, body).await
+}
@@ -3,6 +3,7 @@ use axum::{
     response::Response,
     Json,
 };
+use http::{header::ACCEPT, HeaderMap};
 use std::sync::Arc;
 
 use crate::{
@@ -12,7 +13,9 @@ use crate::{
 
 pub async fn generate_image(
     State(handler): State<Arc<GenerateImageHandler>>,
+    headers: HeaderMap,
     Json(payload): Json<GenerateRequest>,
 ) -> Result<Response, GenerateError> {
-    handler.handle_generate_request(payload).await
+    let accept = headers.get(ACCEPT).and_then(|value| value.to_str().ok());
+    handler.handle_generate_request(payload, accept).await
 }
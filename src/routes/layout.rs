@@ -0,0 +1,17 @@
+use axum::{extract::State, Json};
+use std::sync::Arc;
+
+use crate::{
+    handlers::layout::LayoutHandler,
+    models::{
+        generate::{GenerateError, GenerateRequest},
+        layout::LayoutResponse,
+    },
+};
+
+pub async fn debug_layout(
+    State(handler): State<Arc<LayoutHandler>>,
+    Json(payload): Json<GenerateRequest>,
+) -> Result<Json<LayoutResponse>, GenerateError> {
+    handler.handle_layout_request(payload).await
+}
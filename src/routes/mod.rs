@@ -1,2 +1,8 @@
+pub mod batch;
+pub mod editor;
 pub mod generate;
+pub mod layout;
+pub mod registry;
 pub mod static_files;
+pub mod templates;
+pub mod verify;
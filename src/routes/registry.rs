@@ -0,0 +1,26 @@
+use axum::{
+    body::Bytes,
+    extract::{Path, State},
+    Json,
+};
+use std::sync::Arc;
+
+use crate::{
+    handlers::registry::RegistryHandler,
+    models::registry::{FlagRequest, LookupResponse, RegistryError},
+};
+
+pub async fn lookup_image(
+    State(handler): State<Arc<RegistryHandler>>,
+    body: Bytes,
+) -> Result<Json<LookupResponse>, RegistryError> {
+    handler.handle_lookup_request(body).await
+}
+
+pub async fn flag_render(
+    State(handler): State<Arc<RegistryHandler>>,
+    Path(render_id): Path<String>,
+    Json(request): Json<FlagRequest>,
+) -> Result<Json<serde_json::Value>, RegistryError> {
+    handler.handle_flag_request(&render_id, request).await
+}
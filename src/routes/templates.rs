@@ -0,0 +1,25 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Response,
+    Json,
+};
+use std::sync::Arc;
+
+use crate::{
+    handlers::templates::TemplatesHandler,
+    models::templates::TemplateSummary,
+};
+
+pub async fn list_templates(
+    State(handler): State<Arc<TemplatesHandler>>,
+) -> Json<Vec<TemplateSummary>> {
+    handler.list_templates()
+}
+
+pub async fn template_preview(
+    State(handler): State<Arc<TemplatesHandler>>,
+    Path(id): Path<String>,
+) -> Result<Response, StatusCode> {
+    handler.preview(&id)
+}
@@ -0,0 +1,14 @@
+use axum::{body::Bytes, extract::State, Json};
+use std::sync::Arc;
+
+use crate::{
+    handlers::verify::VerifyHandler,
+    models::verify::{VerifyError, VerifyResponse},
+};
+
+pub async fn verify_image(
+    State(handler): State<Arc<VerifyHandler>>,
+    body: Bytes,
+) -> Result<Json<VerifyResponse>, VerifyError> {
+    handler.handle_verify_request(body).await
+}
@@ -0,0 +1,53 @@
+use std::io::{self, BufWriter, Write};
+
+use axum::body::Bytes;
+use tokio::sync::mpsc::{Receiver, Sender};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+/// Bytes collected before a chunk is handed to the response body.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// A file to add to a streamed archive.
+#[derive(Debug)]
+pub struct ArchiveFile {
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+/// Writes the files received on `files` into a ZIP archive and sends the
+/// archive to `body` in chunks as it is produced. The archive is finished once
+/// `files` is closed. Blocks, so it has to run on a blocking thread.
+///
+/// Returns early with an error when the receiving side of `body` is dropped,
+/// i.e. the client went away.
+pub fn write_zip(mut files: Receiver<ArchiveFile>, body: Sender<io::Result<Bytes>>) -> io::Result<()> {
+    let writer = BufWriter::with_capacity(CHUNK_SIZE, BodyWriter(body));
+    let mut zip = ZipWriter::new_stream(writer);
+
+    // Documents are already compressed images; deflating them again only costs CPU.
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+
+    while let Some(file) = files.blocking_recv() {
+        zip.start_file(file.name, stored)?;
+        zip.write_all(&file.data)?;
+    }
+
+    zip.finish()?.into_inner().flush()
+}
+
+/// Adapter that forwards written bytes to a streaming response body.
+struct BodyWriter(Sender<io::Result<Bytes>>);
+
+impl Write for BodyWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .blocking_send(Ok(Bytes::copy_from_slice(buf)))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "Response body was dropped"))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
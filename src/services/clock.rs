@@ -0,0 +1,51 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use chrono_tz::Tz;
+
+pub const DEFAULT_TIMEZONE: Tz = chrono_tz::Europe::Kyiv;
+
+/// Source of the current time for rendered documents.
+pub trait Clock: Send + Sync + std::fmt::Debug {
+    fn now(&self) -> DateTime<Tz>;
+
+    /// Calendar date in the clock's timezone.
+    fn today(&self) -> NaiveDate {
+        self.now().date_naive()
+    }
+}
+
+/// Wall-clock time converted into a configured timezone, independent of the
+/// host's local time (which is UTC inside the container).
+#[derive(Debug, Clone)]
+pub struct SystemClock {
+    timezone: Tz,
+}
+
+impl SystemClock {
+    pub fn new(timezone: Tz) -> Self {
+        Self { timezone }
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Tz> {
+        Utc::now().with_timezone(&self.timezone)
+    }
+}
+
+/// Clock frozen at a single instant, for reproducible renders and tests.
+#[derive(Debug, Clone)]
+pub struct FixedClock {
+    time: DateTime<Tz>,
+}
+
+impl FixedClock {
+    pub fn new(time: DateTime<Tz>) -> Self {
+        Self { time }
+    }
+}
+
+impl Clock for FixedClock {
+    fn now(&self) -> DateTime<Tz> {
+        self.time
+    }
+}
@@ -0,0 +1,359 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use chrono::{DateTime, Utc};
+use image::GrayImage;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use epovistka_core::image_generator::GeneratedImage;
+use epovistka_core::perceptual_hash::{PerceptualHash, RegionHash};
+
+/// Largest number of differing bits, in both whole-page hashes, at which an
+/// image can still be a copy of a registered one. Every render of a template
+/// is this close to every other, so this only narrows down the template.
+pub const MATCH_DISTANCE: u32 = 8;
+/// Largest number of differing bits, in both hashes of a text region, at
+/// which the region is the same as in a registered image.
+pub const REGION_MATCH_DISTANCE: u32 = 16;
+/// Number of text regions that may still differ in a copy of a registered
+/// image. Resizing and recompression can throw a single region off, mostly
+/// one holding little more than the form's rules; other text in a field
+/// changes several.
+pub const REGION_MISMATCHES: usize = 1;
+/// Renders kept in memory unless `EPOVISTKA_REGISTRY_CAPACITY` says otherwise.
+pub const DEFAULT_CAPACITY: usize = 100_000;
+
+/// A document the service produced. Holds nothing about the person it was
+/// made out to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IssuedImage {
+    pub render_id: String,
+    pub template: String,
+    pub rendered_at: DateTime<Utc>,
+    pub hash: PerceptualHash,
+    /// Hashes of the document's text regions; empty for entries recorded
+    /// before regions were hashed, which match on the page hash alone.
+    #[serde(default)]
+    pub regions: Vec<RegionHash>,
+}
+
+/// An admin's note that a render was used abusively.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Flag {
+    pub reason: String,
+    pub flagged_at: DateTime<Utc>,
+}
+
+/// A registered image similar to the one looked up.
+#[derive(Debug, Clone, Serialize)]
+pub struct Match {
+    #[serde(flatten)]
+    pub image: IssuedImage,
+    pub dhash_distance: u32,
+    pub phash_distance: u32,
+    /// Number of text regions further than [`REGION_MATCH_DISTANCE`] apart.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub differing_regions: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flag: Option<Flag>,
+}
+
+/// Line of the registry file.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum Entry {
+    Issued(IssuedImage),
+    Flagged {
+        render_id: String,
+        #[serde(flatten)]
+        flag: Flag,
+    },
+}
+
+#[derive(Debug, Default)]
+struct Records {
+    images: VecDeque<IssuedImage>,
+    flags: HashMap<String, Flag>,
+}
+
+impl Records {
+    /// Adds `image`, dropping the oldest renders and their flags beyond
+    /// `capacity`.
+    fn push(&mut self, image: IssuedImage, capacity: usize) {
+        self.images.push_back(image);
+        while self.images.len() > capacity {
+            if let Some(dropped) = self.images.pop_front() {
+                self.flags.remove(&dropped.render_id);
+            }
+        }
+    }
+}
+
+/// Opt-in record of every document issued, kept as an append-only JSON lines
+/// file. The latest renders, up to a capacity, are mirrored in memory for
+/// lookups; older ones stay in the file but no longer match.
+#[derive(Debug)]
+pub struct IssuedRegistry {
+    path: PathBuf,
+    capacity: usize,
+    records: Mutex<Records>,
+    file: Mutex<File>,
+}
+
+impl IssuedRegistry {
+    /// Opens the registry file, creating it if needed, and loads its latest
+    /// `capacity` renders.
+    pub fn open(path: impl AsRef<Path>, capacity: usize) -> Result<Self, Box<dyn std::error::Error>> {
+        let path = path.as_ref().to_path_buf();
+        let mut records = Records::default();
+
+        if path.exists() {
+            let reader = BufReader::new(
+                File::open(&path).map_err(|e| format!("Failed to open registry {}: {}", path.display(), e))?,
+            );
+            for (index, line) in reader.lines().enumerate() {
+                let line = line.map_err(|e| format!("Failed to read registry {}: {}", path.display(), e))?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                // A line cut short by a crash must not make the whole registry unusable.
+                match serde_json::from_str(&line) {
+                    Ok(Entry::Issued(image)) => records.push(image, capacity),
+                    Ok(Entry::Flagged { render_id, flag }) => {
+                        records.flags.insert(render_id, flag);
+                    }
+                    Err(e) => warn!("Skipping line {} of registry {}: {}", index + 1, path.display(), e),
+                }
+            }
+
+            // Flags come after their render, which may have been dropped since.
+            let kept: HashSet<&str> = records.images.iter().map(|image| image.render_id.as_str()).collect();
+            records.flags.retain(|render_id, _| kept.contains(render_id.as_str()));
+        }
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| format!("Failed to open registry {}: {}", path.display(), e))?;
+
+        info!(
+            "Loaded {} issued images and {} flags from {}",
+            records.images.len(),
+            records.flags.len(),
+            path.display()
+        );
+
+        Ok(Self { path, capacity, records: Mutex::new(records), file: Mutex::new(file) })
+    }
+
+    /// Records a rendered document. Failures are logged rather than returned,
+    /// so the registry never stops a document from being delivered.
+    pub fn record(&self, template: &str, rendered_at: DateTime<Utc>, image: &GeneratedImage) {
+        let issued = IssuedImage {
+            render_id: image.render_id.clone(),
+            template: template.to_string(),
+            rendered_at,
+            hash: image.hash,
+            regions: image.regions.clone(),
+        };
+
+        if let Err(e) = self.append(&Entry::Issued(issued.clone())) {
+            warn!("Failed to record render {} in {}: {}", issued.render_id, self.path.display(), e);
+        }
+        if let Ok(mut records) = self.records.lock() {
+            records.push(issued, self.capacity);
+        }
+    }
+
+    /// Registered images that `image`, whose page hash is `hash`, is a copy
+    /// of, closest first: within [`MATCH_DISTANCE`] over the whole page and
+    /// [`REGION_MATCH_DISTANCE`] in all but [`REGION_MISMATCHES`] text
+    /// regions. Regions too small to hash in `image` are left out.
+    pub fn lookup(&self, image: &GrayImage, hash: &PerceptualHash) -> Vec<Match> {
+        let Ok(records) = self.records.lock() else {
+            return Vec::new();
+        };
+
+        // Renders of one template share their regions, so each is hashed once.
+        let mut regions: HashMap<[u32; 4], Option<PerceptualHash>> = HashMap::new();
+        let mut region_hash = |bounds: [f32; 4]| {
+            *regions
+                .entry(bounds.map(f32::to_bits))
+                .or_insert_with(|| RegionHash::of(image, bounds).map(|region| region.hash))
+        };
+
+        let mut matches: Vec<Match> = records
+            .images
+            .iter()
+            .filter_map(|issued| {
+                let (dhash_distance, phash_distance) = issued.hash.distance(hash);
+                if dhash_distance > MATCH_DISTANCE || phash_distance > MATCH_DISTANCE {
+                    return None;
+                }
+
+                let (mut hashed, mut differing) = (0, 0);
+                for region in &issued.regions {
+                    let Some(other) = region_hash(region.bounds) else {
+                        continue;
+                    };
+                    let (dhash, phash) = region.hash.distance(&other);
+                    hashed += 1;
+                    if dhash.max(phash) > REGION_MATCH_DISTANCE {
+                        differing += 1;
+                    }
+                }
+                if differing > REGION_MISMATCHES {
+                    return None;
+                }
+                let differing_regions = (hashed > 0).then_some(differing);
+
+                Some(Match {
+                    image: issued.clone(),
+                    dhash_distance,
+                    phash_distance,
+                    differing_regions,
+                    flag: records.flags.get(&issued.render_id).cloned(),
+                })
+            })
+            .collect();
+
+        matches.sort_by_key(|m| (m.differing_regions, m.dhash_distance + m.phash_distance));
+        matches
+    }
+
+    /// Flags a registered render. Returns `Ok(false)` if the render id is unknown.
+    pub fn flag(&self, render_id: &str, reason: String, flagged_at: DateTime<Utc>) -> std::io::Result<bool> {
+        let Ok(mut records) = self.records.lock() else {
+            return Err(std::io::Error::other("Registry lock poisoned"));
+        };
+        if !records.images.iter().any(|image| image.render_id == render_id) {
+            return Ok(false);
+        }
+
+        let flag = Flag { reason, flagged_at };
+        self.append(&Entry::Flagged { render_id: render_id.to_string(), flag: flag.clone() })?;
+        records.flags.insert(render_id.to_string(), flag);
+
+        Ok(true)
+    }
+
+    fn append(&self, entry: &Entry) -> std::io::Result<()> {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+
+        let mut file = self.file.lock().map_err(|_| std::io::Error::other("Registry lock poisoned"))?;
+        file.write_all(&line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use chrono::NaiveDate;
+    use epovistka_core::image_generator::{RenderOptions, RenderRequest};
+    use epovistka_core::output::{OutputFormat, OutputOptions};
+    use epovistka_core::template_registry::TemplateRegistry;
+    use image::imageops::FilterType;
+    use image::ImageFormat;
+
+    use super::*;
+
+    fn render(templates: &TemplateRegistry, name: &str, address: &str, seed: u64) -> GeneratedImage {
+        let request = RenderRequest { name: name.into(), address: address.into(), seed: Some(seed), locale: None };
+        let options = RenderOptions {
+            today: NaiveDate::from_ymd_opt(2025, 3, 14).unwrap(),
+            rendered_at: DateTime::from_timestamp(1_741_950_000, 0).unwrap(),
+            output: OutputOptions { format: OutputFormat::Png, quality: None, paper: None },
+            watermark: None,
+            fingerprint: None,
+        };
+        templates.get(None).unwrap().generate_image(&request, &options).unwrap()
+    }
+
+    fn lookup(registry: &IssuedRegistry, data: &[u8]) -> Vec<Match> {
+        let image = image::load_from_memory(data).unwrap().to_luma8();
+        registry.lookup(&image, &PerceptualHash::of(&image))
+    }
+
+    #[test]
+    fn only_copies_of_a_render_match_it() {
+        let path = std::env::temp_dir().join(format!("epovistka-registry-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let registry = IssuedRegistry::open(&path, DEFAULT_CAPACITY).unwrap();
+        let templates = TemplateRegistry::load(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/templates")).unwrap();
+
+        let issued = render(&templates, "Шевченко Тарас Григорович", "м. Київ, вул. Хрещатик, 22, кв. 5", 1);
+        registry.record("default", Utc::now(), &issued);
+
+        for (name, address, seed) in [
+            ("Франко Іван Якович", "м. Львів, вул. Личаківська, 8", 1),
+            ("Франко Іван Якович", "м. Київ, вул. Хрещатик, 22, кв. 5", 1),
+            ("Українка Леся", "м. Луцьк, пр. Волі, 14, кв. 3", 7),
+            ("Лі Ян", "м. Київ", 1),
+        ] {
+            let other = render(&templates, name, address, seed);
+            assert!(lookup(&registry, &other.data).is_empty(), "{} (seed {}) matched", name, seed);
+        }
+
+        let decoded = image::load_from_memory(&issued.data).unwrap();
+        let mut jpeg = Vec::new();
+        decoded.to_rgb8().write_to(&mut Cursor::new(&mut jpeg), ImageFormat::Jpeg).unwrap();
+        let mut halved = Vec::new();
+        decoded
+            .resize(decoded.width() / 2, decoded.height() / 2, FilterType::Triangle)
+            .write_to(&mut Cursor::new(&mut halved), ImageFormat::Png)
+            .unwrap();
+
+        for (copy, data) in [("original", &issued.data), ("JPEG", &jpeg), ("half size", &halved)] {
+            let matches = lookup(&registry, data);
+            assert_eq!(matches.len(), 1, "{} copy", copy);
+            assert_eq!(matches[0].image.render_id, issued.render_id);
+        }
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn keeps_the_latest_renders_up_to_its_capacity() {
+        let path = std::env::temp_dir().join(format!("epovistka-registry-capacity-{}.jsonl", std::process::id()));
+        let issued = |render_id: &str| IssuedImage {
+            render_id: render_id.to_string(),
+            template: "default".to_string(),
+            rendered_at: Utc::now(),
+            hash: PerceptualHash { dhash: 0, phash: 0 },
+            regions: Vec::new(),
+        };
+        let flag = |render_id: &str| Entry::Flagged {
+            render_id: render_id.to_string(),
+            flag: Flag { reason: "abuse".to_string(), flagged_at: Utc::now() },
+        };
+        let lines: Vec<String> =
+            [Entry::Issued(issued("a")), Entry::Issued(issued("b")), Entry::Issued(issued("c")), flag("a"), flag("b")]
+                .iter()
+                .map(|entry| serde_json::to_string(entry).unwrap())
+                .collect();
+        std::fs::write(&path, lines.join("\n")).unwrap();
+
+        let registry = IssuedRegistry::open(&path, 2).unwrap();
+        {
+            let records = registry.records.lock().unwrap();
+            let kept: Vec<&str> = records.images.iter().map(|image| image.render_id.as_str()).collect();
+            assert_eq!(kept, ["b", "c"]);
+            assert_eq!(records.flags.keys().collect::<Vec<_>>(), ["b"]);
+        }
+
+        registry.records.lock().unwrap().push(issued("d"), 2);
+        let records = registry.records.lock().unwrap();
+        assert_eq!(records.images.iter().map(|image| image.render_id.as_str()).collect::<Vec<_>>(), ["c", "d"]);
+        assert!(records.flags.is_empty());
+        drop(records);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1 +1,7 @@
-pub mod image_generator;
\ No newline at end of file
+pub mod archive;
+pub mod clock;
+pub mod issued_registry;
+pub mod render_pool;
+pub mod template_store;
+pub mod template_watcher;
+pub mod upload;
@@ -0,0 +1,200 @@
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use thiserror::Error;
+use tokio::sync::{oneshot, Semaphore};
+use tracing::error;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+#[derive(Error, Debug)]
+pub enum PoolError {
+    #[error("Render queue is full")]
+    Overloaded,
+
+    #[error("Render job failed")]
+    Failed,
+}
+
+/// Fixed set of OS threads that run CPU-bound rendering away from the async
+/// runtime. At most `workers + queue` jobs are admitted at a time; the rest
+/// are either rejected ([`RenderPool::run`]) or wait for a slot
+/// ([`RenderPool::run_queued`]). Waiting jobs only ever fill half the
+/// workers, so background work leaves room for jobs that cannot wait.
+#[derive(Debug)]
+pub struct RenderPool {
+    sender: Sender<Job>,
+    capacity: Arc<Semaphore>,
+    queued: Arc<Semaphore>,
+    workers: usize,
+    queued_workers: usize,
+}
+
+impl RenderPool {
+    pub fn new(workers: usize, queue: usize) -> Self {
+        let workers = workers.max(1);
+        let queued_workers = (workers / 2).max(1);
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+
+        for index in 0..workers {
+            let receiver = receiver.clone();
+            thread::Builder::new()
+                .name(format!("render-{}", index))
+                .spawn(move || work(&receiver))
+                .expect("Failed to spawn render worker");
+        }
+
+        Self {
+            sender,
+            capacity: Arc::new(Semaphore::new(workers + queue)),
+            queued: Arc::new(Semaphore::new(queued_workers)),
+            workers,
+            queued_workers,
+        }
+    }
+
+    pub fn workers(&self) -> usize {
+        self.workers
+    }
+
+    /// Jobs [`RenderPool::run_queued`] runs at once, across all its callers.
+    pub fn queued_workers(&self) -> usize {
+        self.queued_workers
+    }
+
+    /// Runs `job` on the pool, failing right away when the queue is full.
+    pub async fn run<T, F>(&self, job: F) -> Result<T, PoolError>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let permit = self.capacity.clone().try_acquire_owned().map_err(|_| PoolError::Overloaded)?;
+        self.submit(permit, job).await
+    }
+
+    /// Runs `job` on the pool, waiting for room in the queue if necessary.
+    /// Only [`RenderPool::queued_workers`] such jobs run or wait for a slot at
+    /// a time, the others wait their turn.
+    pub async fn run_queued<T, F>(&self, job: F) -> Result<T, PoolError>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let turn = self.queued.clone().acquire_owned().await.map_err(|_| PoolError::Failed)?;
+        let permit = self.capacity.clone().acquire_owned().await.map_err(|_| PoolError::Failed)?;
+        self.submit((turn, permit), job).await
+    }
+
+    /// Runs `job` on a worker, holding `permits` until it is done.
+    async fn submit<T, F, P>(&self, permits: P, job: F) -> Result<T, PoolError>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+        P: Send + 'static,
+    {
+        let (result_tx, result_rx) = oneshot::channel();
+        let job: Job = Box::new(move || {
+            // Free the slot before the caller hears back, even from a panic,
+            // so that its next job is not turned away.
+            let result = catch_unwind(AssertUnwindSafe(job));
+            drop(permits);
+            match result {
+                // The caller may have gone away; its result is simply dropped then.
+                Ok(result) => {
+                    let _ = result_tx.send(result);
+                }
+                Err(panic) => resume_unwind(panic),
+            }
+        });
+
+        self.sender.send(job).map_err(|_| PoolError::Failed)?;
+        result_rx.await.map_err(|_| PoolError::Failed)
+    }
+}
+
+fn work(receiver: &Mutex<Receiver<Job>>) {
+    loop {
+        let job = match receiver.lock() {
+            Ok(receiver) => receiver.recv(),
+            Err(_) => return,
+        };
+        let Ok(job) = job else {
+            return;
+        };
+
+        // A panicking render must not take the worker down with it.
+        if catch_unwind(AssertUnwindSafe(job)).is_err() {
+            error!("Render job panicked");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn rejects_jobs_once_the_queue_is_full() {
+        let pool = Arc::new(RenderPool::new(1, 1));
+        let (release, blocked) = mpsc::channel::<()>();
+        let blocked = Arc::new(Mutex::new(blocked));
+
+        // One job on the worker and one in the queue fill the pool.
+        let running: Vec<_> = (0..2)
+            .map(|_| {
+                let pool = pool.clone();
+                let blocked = blocked.clone();
+                tokio::spawn(async move { pool.run(move || blocked.lock().unwrap().recv().is_ok()).await })
+            })
+            .collect();
+        while pool.capacity.available_permits() > 0 {
+            tokio::task::yield_now().await;
+        }
+
+        assert!(matches!(pool.run(|| ()).await, Err(PoolError::Overloaded)));
+
+        release.send(()).unwrap();
+        release.send(()).unwrap();
+        for job in running {
+            assert!(job.await.unwrap().unwrap());
+        }
+        assert!(pool.run(|| ()).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn queued_jobs_leave_workers_free() {
+        let pool = Arc::new(RenderPool::new(2, 0));
+        let (release, blocked) = mpsc::channel::<()>();
+        let blocked = Arc::new(Mutex::new(blocked));
+
+        let queued: Vec<_> = (0..2)
+            .map(|_| {
+                let pool = pool.clone();
+                let blocked = blocked.clone();
+                tokio::spawn(async move { pool.run_queued(move || blocked.lock().unwrap().recv().is_ok()).await })
+            })
+            .collect();
+        // Only one of the two jobs gets a worker; the other one stays free.
+        while pool.queued.available_permits() > 0 || pool.capacity.available_permits() > 1 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(pool.run(|| 1 + 1).await.unwrap(), 2);
+
+        release.send(()).unwrap();
+        release.send(()).unwrap();
+        for job in queued {
+            assert!(job.await.unwrap().unwrap());
+        }
+    }
+
+    #[tokio::test]
+    async fn survives_a_panicking_job() {
+        let pool = RenderPool::new(1, 0);
+
+        assert!(matches!(pool.run(|| panic!("render failed")).await, Err(PoolError::Failed)));
+        assert_eq!(pool.run(|| 1 + 1).await.unwrap(), 2);
+    }
+}
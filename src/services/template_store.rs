@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+use tracing::info;
+
+use epovistka_core::image_generator::ImageGenerator;
+use epovistka_core::template::TemplateManifest;
+use epovistka_core::template_registry::{SharedRegistry, TemplateRegistry, FONTS_DIR, MANIFEST_FILE};
+
+use crate::models::editor::EditorError;
+
+/// Longest template id and asset name accepted.
+const MAX_NAME_LENGTH: usize = 64;
+
+/// Suffix of a manifest that is written but not yet checked.
+const PENDING_SUFFIX: &str = ".pending";
+
+const IMAGE_EXTENSIONS: [&str; 4] = ["png", "jpg", "jpeg", "webp"];
+const FONT_EXTENSIONS: [&str; 2] = ["ttf", "otf"];
+
+/// Writes templates into the templates directory for the template editor and
+/// puts saved templates into service right away.
+#[derive(Debug)]
+pub struct TemplateStore {
+    dir: PathBuf,
+    registry: SharedRegistry,
+    /// Held while a template's files are written, so that concurrent saves
+    /// of one template neither share pending files nor publish out of order.
+    locks: Mutex<HashMap<String, Arc<Mutex<()>>>>,
+}
+
+impl TemplateStore {
+    pub fn new(dir: impl Into<PathBuf>, registry: SharedRegistry) -> Self {
+        Self { dir: dir.into(), registry, locks: Mutex::default() }
+    }
+
+    /// The manifest file of a template, as stored.
+    pub fn manifest(&self, id: &str) -> Result<Vec<u8>, EditorError> {
+        read(&self.template_dir(id)?.join(MANIFEST_FILE), id)
+    }
+
+    /// Images and fonts in a template's directory.
+    pub fn assets(&self, id: &str) -> Result<Vec<String>, EditorError> {
+        let dir = self.template_dir(id)?;
+        let entries = std::fs::read_dir(&dir).map_err(|_| EditorError::NotFound(format!("template '{}'", id)))?;
+
+        let mut assets: Vec<String> = entries
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+            .filter(|name| asset_kind(name).is_some())
+            .collect();
+        assets.sort();
+        Ok(assets)
+    }
+
+    pub fn asset(&self, id: &str, name: &str) -> Result<Vec<u8>, EditorError> {
+        let path = self.template_dir(id)?.join(checked_asset_name(name)?);
+        read(&path, name)
+    }
+
+    /// Stores an image or font after checking that it decodes, replacing any
+    /// file of the same name. Creates the template's directory if needed.
+    pub fn save_asset(&self, id: &str, name: &str, data: &[u8]) -> Result<(), EditorError> {
+        let dir = self.template_dir(id)?;
+        let name = checked_asset_name(name)?;
+
+        match asset_kind(name) {
+            Some(AssetKind::Image) => {
+                image::load_from_memory(data)
+                    .map_err(|e| EditorError::ValidationError(format!("Could not read image '{}': {}", name, e)))?;
+            }
+            Some(AssetKind::Font) => {
+                if rusttype::Font::try_from_bytes(data).is_none() {
+                    return Err(EditorError::ValidationError(format!("Could not read font '{}'", name)));
+                }
+            }
+            None => unreachable!("checked_asset_name only accepts known extensions"),
+        }
+
+        let lock = self.lock(id)?;
+        let _saving = lock.lock().map_err(poisoned)?;
+        std::fs::create_dir_all(&dir).map_err(failed)?;
+        write_atomically(&dir.join(name), data)?;
+
+        info!("Saved asset '{}' of template '{}'", name, id);
+        Ok(())
+    }
+
+    /// Checks a manifest the way the registry loads templates, including a
+    /// test render, and only then replaces the template's manifest and swaps
+    /// the template into the registry. A manifest that does not load leaves
+    /// the stored template untouched.
+    pub fn save_manifest(&self, id: &str, manifest: &serde_json::Value) -> Result<(), EditorError> {
+        let dir = self.template_dir(id)?;
+        let data = serde_json::to_vec_pretty(manifest).map_err(failed)?;
+
+        let lock = self.lock(id)?;
+        let _saving = lock.lock().map_err(poisoned)?;
+        std::fs::create_dir_all(&dir).map_err(failed)?;
+        // The registry only picks up manifest.json, so the pending file is never served
+        let pending = dir.join(format!("{}{}", MANIFEST_FILE, PENDING_SUFFIX));
+        std::fs::write(&pending, &data).map_err(failed)?;
+
+        let entry = match TemplateRegistry::load_template(&pending) {
+            Ok(entry) => entry,
+            Err(e) => {
+                let _ = std::fs::remove_file(&pending);
+                return Err(EditorError::InvalidManifest(e.to_string()));
+            }
+        };
+        std::fs::rename(&pending, dir.join(MANIFEST_FILE)).map_err(failed)?;
+
+        self.registry.rcu(|current| Arc::new(current.with_template(id, entry.clone())));
+
+        info!("Saved template '{}'", id);
+        Ok(())
+    }
+
+    /// Loads a manifest with the template's stored assets without writing or
+    /// publishing anything, for previews of unsaved edits.
+    pub fn draft(&self, id: &str, manifest: serde_json::Value) -> Result<ImageGenerator, EditorError> {
+        let manifest = TemplateManifest::from_value(manifest, self.template_dir(id)?)?;
+        Ok(ImageGenerator::from_manifest(manifest)?)
+    }
+
+    fn lock(&self, id: &str) -> Result<Arc<Mutex<()>>, EditorError> {
+        let mut locks = self.locks.lock().map_err(poisoned)?;
+        Ok(locks.entry(id.to_string()).or_default().clone())
+    }
+
+    fn template_dir(&self, id: &str) -> Result<PathBuf, EditorError> {
+        let valid = id.len() <= MAX_NAME_LENGTH
+            && id.starts_with(|c: char| c.is_ascii_alphanumeric())
+            && id.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_');
+        if !valid {
+            return Err(EditorError::ValidationError(format!(
+                "Template id has to be up to {} lowercase letters, digits, '-' or '_'",
+                MAX_NAME_LENGTH
+            )));
+        }
+        if id == FONTS_DIR {
+            return Err(EditorError::ValidationError(format!("'{}' holds the shared fonts, not a template", id)));
+        }
+
+        Ok(self.dir.join(id))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AssetKind {
+    Image,
+    Font,
+}
+
+fn asset_kind(name: &str) -> Option<AssetKind> {
+    let extension = Path::new(name).extension()?.to_str()?.to_ascii_lowercase();
+    if IMAGE_EXTENSIONS.contains(&extension.as_str()) {
+        Some(AssetKind::Image)
+    } else if FONT_EXTENSIONS.contains(&extension.as_str()) {
+        Some(AssetKind::Font)
+    } else {
+        None
+    }
+}
+
+/// Accepts plain file names of images and fonts, nothing that could leave
+/// the template's directory.
+fn checked_asset_name(name: &str) -> Result<&str, EditorError> {
+    let valid = name.len() <= MAX_NAME_LENGTH
+        && name.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        && asset_kind(name).is_some();
+    if !valid {
+        return Err(EditorError::ValidationError(format!(
+            "Asset name has to be a plain file name ending in .{} or .{}",
+            IMAGE_EXTENSIONS.join(", ."),
+            FONT_EXTENSIONS.join(", .")
+        )));
+    }
+
+    Ok(name)
+}
+
+fn read(path: &Path, what: &str) -> Result<Vec<u8>, EditorError> {
+    std::fs::read(path).map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => EditorError::NotFound(what.to_string()),
+        _ => failed(e),
+    })
+}
+
+/// Writes next to `path` and renames, so the template watcher never sees a
+/// half-written file.
+fn write_atomically(path: &Path, data: &[u8]) -> Result<(), EditorError> {
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(PENDING_SUFFIX);
+
+    std::fs::write(&partial, data).map_err(failed)?;
+    std::fs::rename(&partial, path).map_err(failed)
+}
+
+fn failed(e: impl std::fmt::Display) -> EditorError {
+    EditorError::Failed(e.to_string())
+}
+
+fn poisoned<T>(_: PoisonError<T>) -> EditorError {
+    EditorError::Failed("Template lock poisoned".to_string())
+}
@@ -0,0 +1,63 @@
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{error, info, warn};
+
+use epovistka_core::template_registry::{SharedRegistry, TemplateRegistry};
+
+/// Quiet period after the last file event before templates are rebuilt, so an
+/// editor saving several files at once triggers a single reload.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Watches the templates directory and swaps a freshly built registry into
+/// `registry` whenever its contents change. Renders already in flight keep
+/// the generator they started with. Only changes under `dir` are seen, which
+/// is why assets shared between templates live in `dir` too (`fonts/`).
+pub fn spawn(dir: PathBuf, registry: SharedRegistry) -> Result<(), notify::Error> {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+
+    let mut watcher = RecommendedWatcher::new(
+        move |result: notify::Result<Event>| match result {
+            Ok(event) if is_relevant(&event.kind) => {
+                let _ = tx.send(());
+            }
+            Ok(_) => {}
+            Err(e) => warn!("Template watcher error: {}", e),
+        },
+        notify::Config::default(),
+    )?;
+    watcher.watch(&dir, RecursiveMode::Recursive)?;
+
+    info!("Watching {} for template changes", dir.display());
+
+    tokio::spawn(async move {
+        // The watcher stops as soon as it is dropped, so it lives in this task.
+        let _watcher = watcher;
+
+        while rx.recv().await.is_some() {
+            // Wait until the directory has been quiet for a moment.
+            while let Ok(Some(())) = tokio::time::timeout(DEBOUNCE, rx.recv()).await {}
+
+            let dir = dir.clone();
+            let current = registry.load_full();
+            let result = tokio::task::spawn_blocking(move || TemplateRegistry::reload(&dir, &current)).await;
+
+            match result {
+                Ok(Ok(reloaded)) => {
+                    registry.store(Arc::new(reloaded));
+                    info!("Templates reloaded");
+                }
+                Ok(Err(e)) => error!("Template reload rejected: {}", e),
+                Err(e) => error!("Template reload task failed: {}", e),
+            }
+        }
+    });
+
+    Ok(())
+}
+
+fn is_relevant(kind: &EventKind) -> bool {
+    matches!(kind, EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_))
+}
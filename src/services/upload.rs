@@ -0,0 +1,37 @@
+use image::{DynamicImage, ImageReader, Limits};
+use std::io::Cursor;
+
+use crate::models::verify::{MAX_UPLOAD_ALLOC, MAX_UPLOAD_SIDE};
+
+/// Decodes an uploaded image. A small file can claim huge dimensions, so the
+/// size and memory the decoder may use are capped before any pixels are read.
+pub fn decode_image(data: &[u8]) -> Result<DynamicImage, String> {
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_UPLOAD_SIDE);
+    limits.max_image_height = Some(MAX_UPLOAD_SIDE);
+    limits.max_alloc = Some(MAX_UPLOAD_ALLOC);
+
+    let mut reader = ImageReader::new(Cursor::new(data)).with_guessed_format().map_err(|e| e.to_string())?;
+    reader.limits(limits);
+    reader.decode().map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{GrayImage, ImageFormat};
+
+    use super::*;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut data = Vec::new();
+        GrayImage::new(width, height).write_to(&mut Cursor::new(&mut data), ImageFormat::Png).unwrap();
+        data
+    }
+
+    #[test]
+    fn rejects_images_larger_than_the_limits() {
+        assert!(decode_image(&png(1000, 1250)).is_ok());
+        assert!(decode_image(&png(MAX_UPLOAD_SIDE + 1, 1)).is_err());
+        assert!(decode_image(b"not an image").is_err());
+    }
+}
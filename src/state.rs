@@ -0,0 +1,60 @@
+use axum::extract::FromRef;
+use std::sync::Arc;
+
+use crate::handlers::{
+    batch::BatchHandler, editor::EditorHandler, generate::GenerateImageHandler, layout::LayoutHandler, registry::RegistryHandler,
+    templates::TemplatesHandler, verify::VerifyHandler,
+};
+
+#[derive(Clone)]
+pub struct AppState {
+    pub generate: Arc<GenerateImageHandler>,
+    pub batch: Arc<BatchHandler>,
+    pub templates: Arc<TemplatesHandler>,
+    pub verify: Arc<VerifyHandler>,
+    pub registry: Arc<RegistryHandler>,
+    pub layout: Arc<LayoutHandler>,
+    pub editor: Arc<EditorHandler>,
+}
+
+impl FromRef<AppState> for Arc<GenerateImageHandler> {
+    fn from_ref(state: &AppState) -> Self {
+        state.generate.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<BatchHandler> {
+    fn from_ref(state: &AppState) -> Self {
+        state.batch.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<TemplatesHandler> {
+    fn from_ref(state: &AppState) -> Self {
+        state.templates.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<VerifyHandler> {
+    fn from_ref(state: &AppState) -> Self {
+        state.verify.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<RegistryHandler> {
+    fn from_ref(state: &AppState) -> Self {
+        state.registry.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<LayoutHandler> {
+    fn from_ref(state: &AppState) -> Self {
+        state.layout.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<EditorHandler> {
+    fn from_ref(state: &AppState) -> Self {
+        state.editor.clone()
+    }
+}
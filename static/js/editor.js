@@ -0,0 +1,667 @@
+(function() {
+    'use strict';
+
+    const TOKEN_KEY = 'editorToken';
+    const MIN_BOX_SIZE = 4;
+
+    class TemplateEditor {
+        constructor() {
+            this.manifest = null;
+            this.templateId = '';
+            this.assets = [];
+            this.imageUrl = null;
+            this.imageSize = null;
+            this.selectedField = -1;
+            this.selectedAnchor = null;
+            this.init();
+        }
+
+        init() {
+            this.$ = (id) => document.getElementById(id);
+            this.$('token').value = sessionStorage.getItem(TOKEN_KEY) || '';
+            this.setupEventListeners();
+        }
+
+        setupEventListeners() {
+            this.$('token').addEventListener('change', (e) => sessionStorage.setItem(TOKEN_KEY, e.target.value.trim()));
+            this.$('openTemplate').addEventListener('click', () => this.openTemplate());
+            this.$('newTemplate').addEventListener('click', () => this.newTemplate());
+
+            this.$('templateName').addEventListener('input', (e) => this.update(() => { this.manifest.name = e.target.value; }));
+            this.$('templateColor').addEventListener('input', (e) => this.update(() => { this.manifest.color = hexToColor(e.target.value); }));
+
+            this.$('baseImage').addEventListener('change', (e) => this.uploadBaseImage(e.target.files[0]));
+            this.$('fontFiles').addEventListener('change', (e) => this.uploadFonts(Array.from(e.target.files)));
+            this.$('mainFont').addEventListener('change', (e) => this.update(() => { this.manifest.font = e.target.value; }));
+            this.$('fallbackFonts').addEventListener('change', (e) => this.update(() => {
+                this.manifest.fallback_fonts = Array.from(e.target.selectedOptions).map(option => option.value);
+            }));
+
+            this.$('addField').addEventListener('click', () => this.addField());
+            this.$('removeField').addEventListener('click', () => this.removeField());
+            this.$('addPosition').addEventListener('click', () => this.addPosition());
+            this.$('removePosition').addEventListener('click', () => this.removePosition());
+            this.setupFieldInputs();
+
+            this.$('save').addEventListener('click', () => this.save());
+            this.$('preview').addEventListener('click', () => this.preview());
+            this.$('layout').addEventListener('click', () => this.checkLayout());
+            this.$('closeResult').addEventListener('click', () => { this.$('result').hidden = true; });
+
+            this.$('manifestJson').addEventListener('change', (e) => this.applyJson(e.target.value));
+        }
+
+        setupFieldInputs() {
+            const field = () => this.manifest.fields[this.selectedField];
+            const number = (id) => parseFloat(this.$(id).value);
+
+            this.$('fieldName').addEventListener('input', (e) => this.update(() => { field().name = e.target.value; }));
+            this.$('fieldSource').addEventListener('change', (e) => this.update(() => {
+                field().source = sourceFor(e.target.value, field().source);
+            }));
+            this.$('fieldText').addEventListener('input', (e) => this.update(() => { field().source.text = e.target.value; }));
+
+            ['sizeMin', 'sizeMax'].forEach(id => this.$(id).addEventListener('input', () => this.update(() => {
+                field().size = [number('sizeMin'), number('sizeMax')];
+            })));
+            ['jitterXMin', 'jitterXMax', 'jitterYMin', 'jitterYMax'].forEach(id => this.$(id).addEventListener('input', () => this.update(() => {
+                field().jitter = {
+                    x: [number('jitterXMin') || 0, number('jitterXMax') || 0],
+                    y: [number('jitterYMin') || 0, number('jitterYMax') || 0],
+                };
+            })));
+
+            const color = () => this.update(() => {
+                if (this.$('fieldOwnColor').checked) {
+                    field().color = hexToColor(this.$('fieldColor').value);
+                } else {
+                    delete field().color;
+                }
+            });
+            this.$('fieldOwnColor').addEventListener('change', color);
+            this.$('fieldColor').addEventListener('input', () => {
+                this.$('fieldOwnColor').checked = true;
+                color();
+            });
+        }
+
+        // API
+
+        async api(method, url, body, contentType) {
+            const headers = { 'Authorization': `Bearer ${this.$('token').value.trim()}` };
+            if (contentType) {
+                headers['Content-Type'] = contentType;
+            }
+
+            const response = await fetch(url, { method, headers, body });
+            if (!response.ok) {
+                let message = `HTTP ${response.status}`;
+                try {
+                    message = (await response.json()).error || message;
+                } catch (_) {
+                    // Not a JSON error body; keep the status
+                }
+                throw new Error(message);
+            }
+            return response;
+        }
+
+        templateUrl(path = '') {
+            return `/admin/templates/${encodeURIComponent(this.templateId)}${path}`;
+        }
+
+        // Loading
+
+        async openTemplate() {
+            if (!this.readTemplateId()) {
+                return;
+            }
+
+            try {
+                this.setStatus('Завантаження...');
+                const response = await this.api('GET', this.templateUrl('/manifest'));
+                this.manifest = await response.json();
+                if (!Array.isArray(this.manifest.fields)) {
+                    this.manifest.fields = [];
+                }
+                await this.loadAssets();
+                await this.loadImage();
+                this.selectField(this.manifest.fields.length > 0 ? 0 : -1);
+                this.render();
+                this.setStatus(`Шаблон «${this.templateId}» відкрито`);
+            } catch (error) {
+                this.showError(error);
+            }
+        }
+
+        newTemplate() {
+            if (!this.readTemplateId()) {
+                return;
+            }
+
+            this.manifest = {
+                name: this.templateId,
+                image: '',
+                font: '',
+                color: [0, 0, 0, 255],
+                fields: [],
+            };
+            this.assets = [];
+            this.setImage(null);
+            this.selectField(-1);
+            this.render();
+            this.setStatus('Завантажте основне зображення та шрифт');
+        }
+
+        readTemplateId() {
+            const id = this.$('templateId').value.trim();
+            if (!/^[a-z0-9][a-z0-9_-]{0,63}$/.test(id)) {
+                this.showError(new Error('Ідентифікатор: малі латинські літери, цифри, «-» та «_»'));
+                return false;
+            }
+            this.templateId = id;
+            return true;
+        }
+
+        async loadAssets() {
+            try {
+                const response = await this.api('GET', this.templateUrl('/assets'));
+                this.assets = (await response.json()).assets;
+            } catch (_) {
+                // A template that has never been saved has no directory yet
+                this.assets = [];
+            }
+        }
+
+        async loadImage() {
+            if (!this.manifest.image) {
+                this.setImage(null);
+                return;
+            }
+
+            try {
+                const response = await this.api('GET', this.templateUrl(`/assets/${encodeURIComponent(this.manifest.image)}`));
+                this.setImage(await response.blob());
+            } catch (error) {
+                this.setImage(null);
+                this.showError(new Error(`Не вдалося завантажити ${this.manifest.image}: ${error.message}`));
+            }
+        }
+
+        setImage(blob) {
+            if (this.imageUrl) {
+                URL.revokeObjectURL(this.imageUrl);
+            }
+            this.imageUrl = blob ? URL.createObjectURL(blob) : null;
+            this.imageSize = null;
+
+            const stage = this.$('stage');
+            stage.innerHTML = '';
+            if (!this.imageUrl) {
+                stage.innerHTML = '<p class="placeholder">Відкрийте шаблон або завантажте основне зображення.</p>';
+                return;
+            }
+
+            const img = document.createElement('img');
+            img.draggable = false;
+            img.alt = '';
+            img.addEventListener('load', () => {
+                this.imageSize = { width: img.naturalWidth, height: img.naturalHeight };
+                this.renderBoxes();
+            });
+            img.src = this.imageUrl;
+            stage.appendChild(img);
+        }
+
+        // Uploads
+
+        async uploadBaseImage(file) {
+            if (!file || !this.requireTemplate()) {
+                return;
+            }
+
+            try {
+                const name = await this.uploadAsset(file);
+                this.update(() => { this.manifest.image = name; });
+                await this.loadImage();
+                this.setStatus(`Зображення ${name} завантажено`);
+            } catch (error) {
+                this.showError(error);
+            }
+            this.$('baseImage').value = '';
+        }
+
+        async uploadFonts(files) {
+            if (files.length === 0 || !this.requireTemplate()) {
+                return;
+            }
+
+            try {
+                for (const file of files) {
+                    const name = await this.uploadAsset(file);
+                    if (!this.manifest.font) {
+                        this.manifest.font = name;
+                    }
+                }
+                this.render();
+                this.setStatus(`Шрифтів завантажено: ${files.length}`);
+            } catch (error) {
+                this.showError(error);
+            }
+            this.$('fontFiles').value = '';
+        }
+
+        async uploadAsset(file) {
+            const name = assetName(file.name);
+            await this.api('PUT', this.templateUrl(`/assets/${encodeURIComponent(name)}`), file, 'application/octet-stream');
+            if (!this.assets.includes(name)) {
+                this.assets.push(name);
+                this.assets.sort();
+            }
+            return name;
+        }
+
+        requireTemplate() {
+            if (!this.manifest) {
+                this.showError(new Error('Спочатку відкрийте або створіть шаблон'));
+                return false;
+            }
+            return true;
+        }
+
+        // Fields
+
+        addField() {
+            if (!this.requireTemplate()) {
+                return;
+            }
+
+            const size = this.imageSize || { width: 1000, height: 1000 };
+            this.manifest.fields.push({
+                name: `field_${this.manifest.fields.length + 1}`,
+                source: { type: 'static', text: 'Текст' },
+                positions: [this.defaultAnchor(size)],
+                size: [28.0, 32.0],
+            });
+            this.selectField(this.manifest.fields.length - 1);
+            this.render();
+        }
+
+        removeField() {
+            this.manifest.fields.splice(this.selectedField, 1);
+            this.selectField(Math.min(this.selectedField, this.manifest.fields.length - 1));
+            this.render();
+        }
+
+        addPosition() {
+            const field = this.manifest.fields[this.selectedField];
+            const anchor = this.defaultAnchor(this.imageSize || { width: 1000, height: 1000 });
+            field.positions = field.positions || [];
+            field.positions.push(anchor);
+            this.selectedAnchor = anchor;
+            this.render();
+        }
+
+        removePosition() {
+            const field = this.manifest.fields[this.selectedField];
+            const positions = field.positions || [];
+            const index = positions.indexOf(this.selectedAnchor);
+            if (index === -1) {
+                this.showError(new Error('Виберіть позицію поля на зображенні'));
+                return;
+            }
+            positions.splice(index, 1);
+            this.selectedAnchor = anchorsOf(field)[0] || null;
+            this.render();
+        }
+
+        defaultAnchor(size) {
+            return {
+                x: Math.round(size.width * 0.1),
+                y: Math.round(size.height * 0.1),
+                width: Math.round(size.width * 0.3),
+                height: 40.0,
+            };
+        }
+
+        selectField(index, anchor) {
+            this.selectedField = index;
+            const field = this.manifest && this.manifest.fields[index];
+            this.selectedAnchor = anchor || (field ? anchorsOf(field)[0] || null : null);
+        }
+
+        // Rendering
+
+        update(change) {
+            if (!this.manifest) {
+                return;
+            }
+            change();
+            this.render();
+        }
+
+        render() {
+            this.renderTemplateInputs();
+            this.renderFieldList();
+            this.renderFieldEditor();
+            this.renderBoxes();
+            this.$('manifestJson').value = this.manifest ? JSON.stringify(this.manifest, null, 2) : '';
+        }
+
+        renderTemplateInputs() {
+            const manifest = this.manifest || {};
+            setValue(this.$('templateName'), manifest.name || '');
+            setValue(this.$('templateColor'), colorToHex(manifest.color || [0, 0, 0, 255]));
+
+            const fonts = this.assets.filter(name => /\.(ttf|otf)$/i.test(name));
+            fillSelect(this.$('mainFont'), fonts, [manifest.font]);
+            fillSelect(this.$('fallbackFonts'), fonts, manifest.fallback_fonts || []);
+        }
+
+        renderFieldList() {
+            const list = this.$('fieldList');
+            list.innerHTML = '';
+            (this.manifest ? this.manifest.fields : []).forEach((field, index) => {
+                const item = document.createElement('li');
+                item.textContent = field.name || '(без назви)';
+                item.classList.toggle('selected', index === this.selectedField);
+                item.addEventListener('click', () => {
+                    this.selectField(index);
+                    this.render();
+                });
+                list.appendChild(item);
+            });
+        }
+
+        renderFieldEditor() {
+            const field = this.manifest && this.manifest.fields[this.selectedField];
+            this.$('fieldEditor').hidden = !field;
+            if (!field) {
+                return;
+            }
+
+            setValue(this.$('fieldName'), field.name);
+            setValue(this.$('fieldSource'), sourceKey(field.source));
+            this.$('fieldTextLabel').hidden = field.source.type !== 'static';
+            setValue(this.$('fieldText'), field.source.text || '');
+
+            const size = field.size || [];
+            setValue(this.$('sizeMin'), size[0] ?? '');
+            setValue(this.$('sizeMax'), size[1] ?? '');
+
+            const jitter = field.jitter || {};
+            setValue(this.$('jitterXMin'), (jitter.x || [])[0] ?? '');
+            setValue(this.$('jitterXMax'), (jitter.x || [])[1] ?? '');
+            setValue(this.$('jitterYMin'), (jitter.y || [])[0] ?? '');
+            setValue(this.$('jitterYMax'), (jitter.y || [])[1] ?? '');
+
+            this.$('fieldOwnColor').checked = Array.isArray(field.color);
+            setValue(this.$('fieldColor'), colorToHex(field.color || this.manifest.color || [0, 0, 0, 255]));
+        }
+
+        renderBoxes() {
+            const stage = this.$('stage');
+            stage.querySelectorAll('.box').forEach(box => box.remove());
+            if (!this.manifest || !this.imageSize) {
+                return;
+            }
+
+            const { width, height } = this.imageSize;
+            const percent = (value, total) => `${(value / total) * 100}%`;
+
+            this.manifest.fields.forEach((field, index) => {
+                anchorsOf(field).forEach(anchor => {
+                    const box = document.createElement('div');
+                    box.className = 'box';
+                    box.classList.toggle('selected', anchor === this.selectedAnchor);
+                    box.classList.toggle('anchor-only', anchor.width == null || anchor.height == null);
+                    box.style.left = percent(anchor.x, width);
+                    box.style.top = percent(anchor.y, height);
+                    box.style.width = anchor.width != null ? percent(anchor.width, width) : '12px';
+                    box.style.height = anchor.height != null ? percent(anchor.height, height) : '12px';
+
+                    const label = document.createElement('span');
+                    label.className = 'box-label';
+                    label.textContent = field.name;
+                    box.appendChild(label);
+
+                    const handle = document.createElement('span');
+                    handle.className = 'handle';
+                    box.appendChild(handle);
+
+                    box.addEventListener('pointerdown', (e) => this.startDrag(e, index, anchor, e.target === handle));
+                    stage.appendChild(box);
+                });
+            });
+        }
+
+        // Dragging
+
+        startDrag(event, fieldIndex, anchor, resizing) {
+            event.preventDefault();
+            const box = event.currentTarget;
+            box.setPointerCapture(event.pointerId);
+
+            if (fieldIndex !== this.selectedField || anchor !== this.selectedAnchor) {
+                this.selectField(fieldIndex, anchor);
+                this.renderFieldList();
+                this.renderFieldEditor();
+                this.$('stage').querySelectorAll('.box.selected').forEach(other => other.classList.remove('selected'));
+                box.classList.add('selected');
+            }
+
+            // Screen pixels per template pixel
+            const scale = this.$('stage').querySelector('img').clientWidth / this.imageSize.width;
+            const start = { x: event.clientX, y: event.clientY };
+            const initial = { ...anchor };
+            if (resizing) {
+                initial.width = initial.width ?? box.offsetWidth / scale;
+                initial.height = initial.height ?? box.offsetHeight / scale;
+            }
+
+            const move = (e) => {
+                const dx = (e.clientX - start.x) / scale;
+                const dy = (e.clientY - start.y) / scale;
+                if (resizing) {
+                    anchor.width = Math.round(Math.max(MIN_BOX_SIZE, initial.width + dx));
+                    anchor.height = Math.round(Math.max(MIN_BOX_SIZE, initial.height + dy));
+                    box.style.width = `${(anchor.width / this.imageSize.width) * 100}%`;
+                    box.style.height = `${(anchor.height / this.imageSize.height) * 100}%`;
+                    box.classList.remove('anchor-only');
+                } else {
+                    anchor.x = Math.round(clamp(initial.x + dx, 0, this.imageSize.width));
+                    anchor.y = Math.round(clamp(initial.y + dy, 0, this.imageSize.height));
+                    box.style.left = `${(anchor.x / this.imageSize.width) * 100}%`;
+                    box.style.top = `${(anchor.y / this.imageSize.height) * 100}%`;
+                }
+            };
+            const end = () => {
+                box.removeEventListener('pointermove', move);
+                box.removeEventListener('pointerup', end);
+                box.removeEventListener('pointercancel', end);
+                this.$('manifestJson').value = JSON.stringify(this.manifest, null, 2);
+            };
+
+            box.addEventListener('pointermove', move);
+            box.addEventListener('pointerup', end);
+            box.addEventListener('pointercancel', end);
+        }
+
+        applyJson(text) {
+            try {
+                const manifest = JSON.parse(text);
+                if (!Array.isArray(manifest.fields)) {
+                    manifest.fields = [];
+                }
+                const imageChanged = !this.manifest || manifest.image !== this.manifest.image;
+                this.manifest = manifest;
+                this.selectField(Math.min(Math.max(this.selectedField, 0), manifest.fields.length - 1));
+                this.render();
+                if (imageChanged && this.templateId) {
+                    this.loadImage();
+                }
+                this.setStatus('Маніфест оновлено');
+            } catch (error) {
+                this.showError(new Error(`Некоректний JSON: ${error.message}`));
+            }
+        }
+
+        // Saving and previews
+
+        async save() {
+            if (!this.requireTemplate()) {
+                return false;
+            }
+
+            try {
+                this.setStatus('Збереження...');
+                await this.api('PUT', this.templateUrl('/manifest'), JSON.stringify(this.manifest), 'application/json');
+                this.setStatus(`Шаблон «${this.templateId}» збережено`);
+                return true;
+            } catch (error) {
+                this.showError(error);
+                return false;
+            }
+        }
+
+        // The manifest as edited, unsaved, with the request to render it with
+        draftRequest() {
+            const request = {
+                template: this.templateId,
+                name: this.$('previewName').value.trim(),
+                address: this.$('previewAddress').value.trim(),
+                watermark: this.$('previewWatermark').checked,
+            };
+            const seed = this.$('previewSeed').value;
+            if (seed !== '') {
+                request.seed = Number(seed);
+            }
+            return JSON.stringify({ manifest: this.manifest, request });
+        }
+
+        async preview() {
+            if (!this.requireTemplate()) {
+                return;
+            }
+
+            try {
+                this.setStatus('Генерація...');
+                const response = await this.api('POST', this.templateUrl('/preview'), this.draftRequest(), 'application/json');
+                const seed = response.headers.get('X-Render-Seed');
+                this.showResult(URL.createObjectURL(await response.blob()), `Перегляд (seed ${seed})`, []);
+                this.setStatus('');
+            } catch (error) {
+                this.showError(error);
+            }
+        }
+
+        async checkLayout() {
+            if (!this.requireTemplate()) {
+                return;
+            }
+
+            try {
+                this.setStatus('Перевірка розмітки...');
+                const response = await this.api('POST', this.templateUrl('/layout'), this.draftRequest(), 'application/json');
+                const report = await response.json();
+                this.showResult(report.image, `Розмітка (seed ${report.seed})`, report.issues);
+                this.setStatus(report.issues.length === 0 ? 'Текст не виходить за межі' : `Проблем: ${report.issues.length}`);
+            } catch (error) {
+                this.showError(error);
+            }
+        }
+
+        showResult(src, title, issues) {
+            const image = this.$('resultImage');
+            if (image.src.startsWith('blob:')) {
+                URL.revokeObjectURL(image.src);
+            }
+            image.src = src;
+            this.$('resultTitle').textContent = title;
+
+            const list = this.$('issues');
+            list.innerHTML = '';
+            issues.forEach(issue => {
+                const item = document.createElement('li');
+                const where = issue.problem === 'outside_image' ? 'за межами зображення' : 'за межами поля';
+                item.textContent = `${issue.field}: «${issue.text}» — ${where} (символів: ${issue.glyphs})`;
+                list.appendChild(item);
+            });
+
+            const result = this.$('result');
+            result.hidden = false;
+            result.scrollIntoView({ behavior: 'smooth', block: 'start' });
+        }
+
+        setStatus(message) {
+            const status = this.$('status');
+            status.classList.remove('error');
+            status.textContent = message;
+        }
+
+        showError(error) {
+            console.error('Error:', error);
+            const status = this.$('status');
+            status.classList.add('error');
+            status.textContent = `Помилка: ${error.message}`;
+        }
+    }
+
+    // Every box of a field: single-line positions and the lines of each flow
+    function anchorsOf(field) {
+        return (field.positions || []).concat(...(field.flows || []));
+    }
+
+    function sourceKey(source) {
+        return source.type === 'request' ? `request:${source.field}` : source.type;
+    }
+
+    function sourceFor(key, current) {
+        if (key === sourceKey(current)) {
+            return current;
+        }
+        if (key.startsWith('request:')) {
+            return { type: 'request', field: key.slice('request:'.length) };
+        }
+        return key === 'static' ? { type: 'static', text: '' } : { type: key };
+    }
+
+    function assetName(fileName) {
+        return fileName.toLowerCase().replace(/[^a-z0-9._-]+/g, '-').replace(/^[^a-z0-9]+/, '');
+    }
+
+    function colorToHex(color) {
+        return '#' + color.slice(0, 3).map(c => c.toString(16).padStart(2, '0')).join('');
+    }
+
+    function hexToColor(hex) {
+        return [1, 3, 5].map(i => parseInt(hex.slice(i, i + 2), 16)).concat(255);
+    }
+
+    function clamp(value, min, max) {
+        return Math.min(Math.max(value, min), max);
+    }
+
+    // Leaves the input alone while it is being typed into
+    function setValue(input, value) {
+        if (document.activeElement !== input) {
+            input.value = value;
+        }
+    }
+
+    function fillSelect(select, options, selected) {
+        select.innerHTML = '';
+        options.forEach(name => {
+            const option = document.createElement('option');
+            option.value = name;
+            option.textContent = name;
+            option.selected = selected.includes(name);
+            select.appendChild(option);
+        });
+    }
+
+    // Initialize the editor when DOM is loaded
+    document.addEventListener('DOMContentLoaded', () => {
+        new TemplateEditor();
+    });
+})();
@@ -88,6 +88,8 @@
                 }
 
                 const blob = await response.blob();
+                // Printing re-renders the same document as a PDF, so keep what produced it
+                this.lastRequest = { ...data, seed: Number(response.headers.get('X-Render-Seed')) };
                 this.displayImage(blob);
 
             } catch (error) {
@@ -128,8 +130,32 @@
             container.scrollIntoView({ behavior: 'smooth', block: 'end' });
         }
 
-        printImage(imageUrl) {
+        async printImage(imageUrl) {
+            // Open the window right away so popup blockers treat it as user initiated
             const printWindow = window.open('', '_blank');
+
+            try {
+                const response = await fetch('/generate', {
+                    method: 'POST',
+                    headers: {
+                        'Content-Type': 'application/json',
+                    },
+                    body: JSON.stringify({ ...this.lastRequest, format: 'pdf' }),
+                });
+
+                if (response.ok) {
+                    // The PDF carries the physical page size, so the browser prints it to scale
+                    printWindow.location.href = URL.createObjectURL(await response.blob());
+                    return;
+                }
+            } catch (error) {
+                console.error('Error:', error);
+            }
+
+            this.printRaster(printWindow, imageUrl);
+        }
+
+        printRaster(printWindow, imageUrl) {
             printWindow.document.write(`
                 <!DOCTYPE html>
                 <html>